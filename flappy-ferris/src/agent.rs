@@ -0,0 +1,385 @@
+use crate::entities::{BoostType, PlayState};
+use crate::events::GameEvent;
+use crate::players::GameMode;
+use crate::sim::Simulation;
+use rand::Rng;
+use serde::Deserialize;
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Used when --agent-timeout and --agent-default are not given.
+pub const DEFAULT_TIMEOUT_MS: u64 = 100;
+pub const DEFAULT_FLAP: bool = false;
+
+// A bot in another program, started with --agent-stdio, that plays the first player over stdin and stdout.
+// Every tick the game writes one JSON line with the state of the world, for example:
+//   {"tick":120,"state":"play","player":{"x":256,"y":371.5,"velocity":-9,"can_flap":false},
+//    "pipes":[{"x":610,"gap_top":220,"gap_bottom":420,"gap_y":320}],"enemies":[{"x":900,"y":130}],
+//    "boosts":[{"x":700,"y":500,"type":"bonus_life"}],"speed":1,"score":3,"lifes":1}
+// (all on one line) and the bot answers with an action line: {"flap":true} or {"action":"wait"}.
+// The answer can repeat the tick it is for ({"tick":120,"flap":true}), then late answers to earlier ticks are skipped.
+// If no answer comes in time (or the bot has closed its output), the default action is used for the tick.
+// The bot is expected to answer every line, so an answer without a tick that comes after its tick got the default action
+// is skipped too, instead of being used for the next tick (which would keep the bot a tick behind).
+pub struct AgentIo {
+    lines: Receiver<String>,
+    output: Box<dyn Write>,
+    pub timeout: Duration,
+    pub default_flap: bool,
+    tick: u64,
+    // How many ticks got the default action, with their answers still on the way.
+    late: u64,
+    closed: bool,
+}
+impl AgentIo {
+    pub fn new(timeout: Duration, default_flap: bool) -> Self {
+        // Reading stdin blocks, so it is done in its own thread and the lines wait in a channel.
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self::with_io(lines, Box::new(std::io::stdout()), timeout, default_flap)
+    }
+
+    // Plays with the lines that come through the channel and writes the states to output instead of stdin and stdout.
+    pub fn with_io(
+        lines: Receiver<String>,
+        output: Box<dyn Write>,
+        timeout: Duration,
+        default_flap: bool,
+    ) -> Self {
+        Self {
+            lines,
+            output,
+            timeout,
+            default_flap,
+            tick: 0,
+            late: 0,
+            closed: false,
+        }
+    }
+
+    // Sends the state of the world to the bot and waits (up to the timeout) for its action in this tick.
+    pub fn decide(&mut self, sim: &Simulation) -> bool {
+        let tick = self.send_state(sim);
+        if self.closed {
+            return self.default_flap;
+        }
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let line = match self
+                .lines
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    self.late += 1;
+                    return self.default_flap;
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.closed = true;
+                    return self.default_flap;
+                }
+            };
+
+            match read_answer(&line) {
+                Some((Some(for_tick), _)) if for_tick < tick => {
+                    self.late = self.late.saturating_sub(1);
+                }
+                Some((Some(for_tick), _)) if for_tick > tick => (),
+                Some((None, _)) if self.late > 0 => self.late -= 1,
+                Some((_, flap)) => return flap,
+                None => (),
+            }
+        }
+    }
+
+    // Sends the state of the world to the bot without waiting for its action, so a slow bot can not freeze the window.
+    // The answers are used in the order they come, one in every tick, and the default action while there is none.
+    // Like in decide, answers to earlier ticks are skipped.
+    pub fn poll(&mut self, sim: &Simulation) -> bool {
+        let tick = self.send_state(sim);
+
+        loop {
+            match self.lines.try_recv() {
+                Ok(line) => match read_answer(&line) {
+                    Some((Some(for_tick), _)) if for_tick < tick => (),
+                    Some((_, flap)) => return flap,
+                    None => (),
+                },
+                Err(TryRecvError::Empty) => return self.default_flap,
+                Err(TryRecvError::Disconnected) => {
+                    self.closed = true;
+                    return self.default_flap;
+                }
+            }
+        }
+    }
+
+    // Writes the line with the state of the world and returns the tick it is for.
+    fn send_state(&mut self, sim: &Simulation) -> u64 {
+        let tick = self.tick;
+        self.tick += 1;
+
+        if writeln!(self.output, "{}", world_state(sim, tick))
+            .and_then(|_| self.output.flush())
+            .is_err()
+        {
+            self.closed = true;
+        }
+
+        tick
+    }
+
+    // Set after the bot closed its output (or stopped reading the game's).
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+// Plays single player games without a window, one after another, until the bot closes its output.
+// The games are on the courses of seed, seed + 1 and so on (or random ones without a seed).
+pub fn run_headless(agent: &mut AgentIo, seed: Option<u64>) {
+    let mut game = 0;
+    while !agent.is_closed() {
+        let mut sim = Simulation::new(GameMode::Single);
+        let course = seed.map_or_else(|| rand::thread_rng().gen(), |seed| seed.wrapping_add(game));
+        sim.seed(course);
+        sim.play_state = PlayState::Play;
+
+        let mut events: Vec<GameEvent> = Vec::new();
+        while sim.play_state.is_playing() && !agent.is_closed() {
+            let flap = agent.decide(&sim);
+            sim.step(&[flap], &mut events);
+            events.clear();
+        }
+
+        // The last line of a game has its final score in the "dead" state.
+        if !agent.is_closed() {
+            agent.decide(&sim);
+        }
+        eprintln!(
+            "Game {} on seed {}: {} points",
+            game + 1,
+            course,
+            sim.teams[0].score
+        );
+        game += 1;
+    }
+}
+
+// The state of the world as a JSON object on one line.
+pub fn world_state(sim: &Simulation, tick: u64) -> String {
+    let state = match sim.play_state {
+        PlayState::StartScreen => "start",
+        PlayState::Play => "play",
+        PlayState::Dead => "dead",
+    };
+    let entity = &sim.players[0].entity;
+    let team = &sim.teams[sim.players[0].team];
+
+    let pipes: Vec<String> = sim
+        .pipes
+        .iter()
+        .map(|pipe| {
+            let gap_top = pipe.top_zone.y + pipe.top_zone.h;
+            let gap_bottom = pipe.bottom_zone.y;
+            format!(
+                r#"{{"x":{},"gap_top":{},"gap_bottom":{},"gap_y":{}}}"#,
+                pipe.position.x,
+                gap_top,
+                gap_bottom,
+                (gap_top + gap_bottom) / 2.0
+            )
+        })
+        .collect();
+    let enemies: Vec<String> = sim
+        .enemies
+        .iter()
+        .map(|enemy| format!(r#"{{"x":{},"y":{}}}"#, enemy.position.x, enemy.position.y))
+        .collect();
+    let boosts: Vec<String> = sim
+        .boosts
+        .iter()
+        .map(|boost| {
+            format!(
+                r#"{{"x":{},"y":{},"type":"{}"}}"#,
+                boost.position.x,
+                boost.position.y,
+                boost_name(boost.effect)
+            )
+        })
+        .collect();
+
+    format!(
+        r#"{{"tick":{},"state":"{}","player":{{"x":{},"y":{},"velocity":{},"can_flap":{}}},"pipes":[{}],"enemies":[{}],"boosts":[{}],"speed":{},"score":{},"lifes":{}}}"#,
+        tick,
+        state,
+        entity.position.x,
+        entity.position.y,
+        entity.physics.velocity,
+        entity.can_jump,
+        pipes.join(","),
+        enemies.join(","),
+        boosts.join(","),
+        sim.multiplier,
+        team.score,
+        team.lifes
+    )
+}
+
+fn boost_name(effect: BoostType) -> &'static str {
+    match effect {
+        BoostType::SpeedUp => "speed_up",
+        BoostType::SlowDown => "slow_down",
+        BoostType::BonusLife => "bonus_life",
+        BoostType::Revive => "revive",
+    }
+}
+
+// The action of an answer line (with the tick it is for, if given). Empty lines are skipped and invalid ones reported.
+fn read_answer(line: &str) -> Option<(Option<u64>, bool)> {
+    if line.trim().is_empty() {
+        return None;
+    }
+
+    match parse_action(line) {
+        Ok(action) => Some(action),
+        Err(error) => {
+            eprintln!("Invalid agent action '{}': {}", line, error);
+            None
+        }
+    }
+}
+
+// An answer line as the bot sends it.
+#[derive(Deserialize)]
+struct Answer {
+    tick: Option<u64>,
+    flap: Option<bool>,
+    action: Option<String>,
+}
+
+// Reads an action line: a JSON object with "flap" (true or false) or "action" ("flap" or "wait")
+// and optionally the "tick" it is for. Other fields are skipped.
+pub fn parse_action(line: &str) -> Result<(Option<u64>, bool), String> {
+    let answer: Answer = serde_json::from_str(line).map_err(|error| error.to_string())?;
+
+    let flap = match (answer.flap, answer.action.as_deref()) {
+        (Some(flap), _) => flap,
+        (None, Some("flap")) => true,
+        (None, Some("wait")) => false,
+        (None, Some(action)) => return Err(format!("invalid action \"{}\"", action)),
+        (None, None) => return Err("missing \"flap\" or \"action\"".to_string()),
+    };
+
+    Ok((answer.tick, flap))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Sender;
+
+    // A bot that answers through the returned sender. It has to answer quickly, the timeout is short.
+    fn agent(default_flap: bool) -> (AgentIo, Sender<String>) {
+        let (sender, lines) = channel();
+        let agent = AgentIo::with_io(
+            lines,
+            Box::new(std::io::sink()),
+            Duration::from_millis(20),
+            default_flap,
+        );
+
+        (agent, sender)
+    }
+
+    fn answer(sender: &Sender<String>, line: &str) {
+        sender.send(line.to_string()).unwrap();
+    }
+
+    #[test]
+    fn answers_for_the_tick_are_used() {
+        let sim = Simulation::new(GameMode::Single);
+        let (mut agent, sender) = agent(false);
+
+        answer(&sender, r#"{"tick":0,"flap":true}"#);
+        assert!(agent.decide(&sim));
+        answer(&sender, r#"{"tick":1,"action":"wait"}"#);
+        assert!(!agent.decide(&sim));
+        answer(&sender, r#"{"action":"flap"}"#);
+        assert!(agent.decide(&sim));
+    }
+
+    #[test]
+    fn answers_to_earlier_ticks_are_skipped() {
+        let sim = Simulation::new(GameMode::Single);
+        let (mut agent, sender) = agent(false);
+
+        assert!(!agent.decide(&sim));
+        answer(&sender, r#"{"tick":0,"flap":true}"#);
+        answer(&sender, r#"{"tick":1,"flap":false}"#);
+        assert!(!agent.decide(&sim));
+
+        assert!(!agent.poll(&sim));
+        answer(&sender, r#"{"tick":2,"flap":true}"#);
+        answer(&sender, r#"{"tick":3,"flap":false}"#);
+        assert!(!agent.poll(&sim));
+    }
+
+    #[test]
+    fn late_answers_without_a_tick_are_skipped() {
+        let sim = Simulation::new(GameMode::Single);
+        let (mut agent, sender) = agent(false);
+
+        assert!(!agent.decide(&sim));
+        answer(&sender, r#"{"flap":true}"#);
+        answer(&sender, r#"{"flap":false}"#);
+        assert!(!agent.decide(&sim));
+        answer(&sender, r#"{"flap":true}"#);
+        assert!(agent.decide(&sim));
+    }
+
+    #[test]
+    fn closed_input_gives_the_default_action() {
+        let sim = Simulation::new(GameMode::Single);
+        let (mut agent, sender) = agent(true);
+
+        drop(sender);
+        assert!(agent.decide(&sim));
+        assert!(agent.is_closed());
+        assert!(agent.poll(&sim));
+    }
+
+    #[test]
+    fn invalid_lines_are_skipped() {
+        let sim = Simulation::new(GameMode::Single);
+        let (mut agent, sender) = agent(false);
+
+        for line in [
+            "",
+            "flap",
+            r#"{"action":"jump"}"#,
+            r#"{"tick":0}"#,
+            r#"{"flap":"yes"}"#,
+        ] {
+            answer(&sender, line);
+        }
+        answer(&sender, r#"{"tick":0,"flap":true}"#);
+        assert!(agent.decide(&sim));
+
+        assert!(parse_action(r#"{"tick":-1,"flap":true}"#).is_err());
+        assert_eq!(
+            parse_action(r#"{"flap":true,"comment":"skipped"}"#),
+            Ok((None, true))
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use ggez::graphics::Rect;
+use std::collections::HashMap;
+
+// A timed sequence of frames from a sprite sheet.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Clip {
+    pub fps: f32,
+    pub looping: bool,
+    pub frames: Vec<usize>,
+}
+impl Clip {
+    pub fn duration(&self) -> f32 {
+        self.frames.len() as f32 / self.fps
+    }
+}
+
+// Everything from an animation file: the sprite sheet (an image id from the asset manifest), the size of its frames and the named clips.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AnimationSet {
+    pub sheet: String,
+    pub frame_width: f32,
+    pub frame_height: f32,
+    pub clips: HashMap<String, Clip>,
+}
+impl AnimationSet {
+    // The part of the sprite sheet with the given frame, in the normalized coordinates used by DrawParam::src.
+    // Frames are numbered from left to right and then from top to bottom.
+    pub fn frame_rect(&self, frame: usize, sheet_width: f32, sheet_height: f32) -> Rect {
+        let columns = ((sheet_width / self.frame_width) as usize).max(1);
+        let w = self.frame_width / sheet_width;
+        let h = self.frame_height / sheet_height;
+
+        Rect {
+            x: (frame % columns) as f32 * w,
+            y: (frame / columns) as f32 * h,
+            w,
+            h,
+        }
+    }
+}
+
+// Parses an animation file, for example:
+//   sheet ferris_sheet 64 42
+//   clip flap 12 once 1 1 1
+//   clip glide 1 loop 1
+// All invalid lines are returned together instead of stopping at the first one.
+pub fn parse_animation(source: &str) -> Result<AnimationSet, Vec<String>> {
+    let mut sheet = None;
+    let mut clips = HashMap::new();
+    let mut errors = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts[0] {
+            "sheet" if parts.len() == 4 => match (parts[2].parse(), parts[3].parse()) {
+                (Ok(w), Ok(h)) if w > 0.0 && h > 0.0 => sheet = Some((parts[1].to_string(), w, h)),
+                _ => errors.push(format!("line {}: invalid frame size", number + 1)),
+            },
+            "clip" if parts.len() >= 5 => {
+                let fps = parts[2].parse::<f32>().ok().filter(|fps| *fps > 0.0);
+                let looping = match parts[3] {
+                    "loop" => Some(true),
+                    "once" => Some(false),
+                    _ => None,
+                };
+                let frames = parts[4..]
+                    .iter()
+                    .map(|frame| frame.parse::<usize>().ok())
+                    .collect::<Option<Vec<usize>>>();
+
+                match (fps, looping, frames) {
+                    (Some(fps), Some(looping), Some(frames)) => {
+                        clips.insert(
+                            parts[1].to_string(),
+                            Clip {
+                                fps,
+                                looping,
+                                frames,
+                            },
+                        );
+                    }
+                    _ => errors.push(format!(
+                        "line {}: expected 'clip <name> <fps> <loop|once> <frames...>'",
+                        number + 1
+                    )),
+                }
+            }
+            _ => errors.push(format!(
+                "line {}: unknown or incomplete '{}'",
+                number + 1,
+                line
+            )),
+        }
+    }
+
+    match sheet {
+        Some((sheet, frame_width, frame_height)) if errors.is_empty() => Ok(AnimationSet {
+            sheet,
+            frame_width,
+            frame_height,
+            clips,
+        }),
+        None if errors.is_empty() => Err(vec![
+            "missing 'sheet <image id> <width> <height>'".to_string()
+        ]),
+        _ => Err(errors),
+    }
+}
+
+// Plays the clips of an animation set, one at a time.
+pub struct Animator {
+    pub clip: String,
+    pub time: f32,
+}
+impl Animator {
+    pub fn new(clip: &str) -> Self {
+        Self {
+            clip: clip.to_string(),
+            time: 0.0,
+        }
+    }
+
+    // Switches to another clip. Playing the clip that is already playing does nothing.
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.restart(clip);
+        }
+    }
+
+    // Plays a clip from its beginning, even if it is already playing.
+    pub fn restart(&mut self, clip: &str) {
+        self.clip = clip.to_string();
+        self.time = 0.0;
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.time += delta;
+    }
+
+    pub fn is_playing(&self, clip: &str) -> bool {
+        self.clip == clip
+    }
+
+    // One-shot clips are finished after their last frame and looping ones never finish.
+    // Clips missing from the set count as finished, so nothing waits for them.
+    pub fn is_finished(&self, set: &AnimationSet) -> bool {
+        match set.clips.get(&self.clip) {
+            Some(clip) => !clip.looping && self.time >= clip.duration(),
+            None => true,
+        }
+    }
+
+    // The frame of the sprite sheet that should be shown. One-shot clips stay on their last frame.
+    pub fn frame(&self, set: &AnimationSet) -> usize {
+        let clip = match set.clips.get(&self.clip) {
+            Some(clip) if !clip.frames.is_empty() => clip,
+            _ => return 0,
+        };
+
+        let index = (self.time * clip.fps) as usize;
+        if clip.looping {
+            clip.frames[index % clip.frames.len()]
+        } else {
+            clip.frames[index.min(clip.frames.len() - 1)]
+        }
+    }
+}
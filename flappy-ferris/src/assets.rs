@@ -1,55 +1,437 @@
-use ggez::{audio, graphics};
-use ggez::{Context, GameResult};
+use crate::animation::{self, AnimationSet};
+use ggez::{audio, filesystem, graphics};
+use ggez::{Context, GameError, GameResult};
+use std::collections::HashMap;
+use std::io::Read;
+
+// The manifest that lists every resource the game loads (relative to the resources folder).
+pub const MANIFEST_PATH: &str = "/assets.manifest";
+
+// Every folder in here with a theme manifest is a theme. Its manifest overrides entries of the main one.
+pub const THEMES_PATH: &str = "/themes";
+pub const THEME_MANIFEST: &str = "theme.manifest";
+// The theme that uses only the main manifest.
+pub const DEFAULT_THEME: &str = "classic";
+
+// The ids the game looks up, so every manifest (with its theme applied) has to list them.
+pub const REQUIRED_ASSETS: &[(AssetKind, &str)] = &[
+    (AssetKind::Image, "pipe_top"),
+    (AssetKind::Image, "pipe_bottom"),
+    (AssetKind::Image, "background"),
+    (AssetKind::Image, "layer_hills"),
+    (AssetKind::Image, "layer_city"),
+    (AssetKind::Image, "layer_ground"),
+    (AssetKind::Image, "heart"),
+    (AssetKind::Animation, "ferris"),
+    (AssetKind::Animation, "enemy"),
+    (AssetKind::Animation, "boosts"),
+    (AssetKind::Sound, "boost"),
+    (AssetKind::Sound, "death"),
+    (AssetKind::Sound, "flap"),
+    (AssetKind::Sound, "score"),
+    (AssetKind::Sound, "life_lost"),
+    (AssetKind::Sound, "enemy_pass"),
+    (AssetKind::Sound, "boost_end"),
+    (AssetKind::Sound, "menu_move"),
+    (AssetKind::Sound, "menu_select"),
+    (AssetKind::Sound, "music_menu"),
+    (AssetKind::Sound, "music_gameplay"),
+    (AssetKind::Font, "main"),
+    (AssetKind::Color, "clear"),
+];
+
+// The kinds of resources that can be listed in the manifest.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AssetKind {
+    Image,
+    Sound,
+    Font,
+    Animation,
+    Color,
+}
+
+// A single line of the manifest, e.g. "image ferris_sheet /ferris_sheet.png".
+// Colors have a hex value instead of a path, e.g. "color clear #4dc1cb".
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ManifestEntry {
+    pub kind: AssetKind,
+    pub id: String,
+    pub path: String,
+}
+
+// Parses the whole manifest. Empty lines and lines starting with '#' are skipped.
+// All invalid lines are returned together instead of stopping at the first one.
+pub fn parse_manifest(source: &str) -> Result<Vec<ManifestEntry>, Vec<String>> {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 3 {
+            errors.push(format!(
+                "line {}: expected '<kind> <id> <path>', found '{}'",
+                number + 1,
+                line
+            ));
+            continue;
+        }
+
+        let kind = match parts[0] {
+            "image" => AssetKind::Image,
+            "sound" => AssetKind::Sound,
+            "font" => AssetKind::Font,
+            "animation" => AssetKind::Animation,
+            "color" => AssetKind::Color,
+            other => {
+                errors.push(format!(
+                    "line {}: unknown asset kind '{}'",
+                    number + 1,
+                    other
+                ));
+                continue;
+            }
+        };
+
+        entries.push(ManifestEntry {
+            kind,
+            id: parts[1].to_string(),
+            path: parts[2].to_string(),
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(entries)
+    } else {
+        Err(errors)
+    }
+}
+
+// Parses colors in the "#rrggbb" or "#rrggbbaa" format.
+pub fn parse_color(value: &str) -> Option<graphics::Color> {
+    let hex = value.strip_prefix('#')?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+
+    Some(graphics::Color::from_rgba(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        alpha,
+    ))
+}
+
+// Reads a text file from the resources folder.
+pub fn read_resource(ctx: &mut Context, path: &str) -> GameResult<String> {
+    let mut file = filesystem::open(ctx, path)?;
+    let mut source = String::new();
+    file.read_to_string(&mut source)?;
+
+    Ok(source)
+}
 
 // All the resources needed for playing sound effects and displaying graphics on screen.
+// They are looked up by the ids given to them in the manifest.
 pub struct Assets {
-    pub ferris_stable_image: graphics::Image,
-    pub ferris_jumping_image: graphics::Image,
-    pub enemy_image: graphics::Image,
-    pub boost_life_image: graphics::Image,
-    pub boost_slow_down_image: graphics::Image,
-    pub boost_speed_up_image: graphics::Image,
-    pub pipe_top_image: graphics::Image,
-    pub pipe_bottom_image: graphics::Image,
-    pub background_image: graphics::Image,
-    pub logo_start_screen_image: graphics::Image,
-    pub logo_game_over_image: graphics::Image,
-
-    pub boost_sound: audio::Source,
-    pub death_sound: audio::Source,
+    images: HashMap<String, graphics::Image>,
+    sounds: HashMap<String, audio::Source>,
+    fonts: HashMap<String, graphics::Font>,
+    animations: HashMap<String, AnimationSet>,
+    colors: HashMap<String, graphics::Color>,
+
+    // The entries of the main manifest and the ones that are loaded at the moment (with the theme applied).
+    base: Vec<ManifestEntry>,
+    loaded: HashMap<String, ManifestEntry>,
+    pub theme: String,
 }
 impl Assets {
     pub fn new(ctx: &mut Context) -> GameResult<Assets> {
-        let ferris_stable_image = graphics::Image::new(ctx, "/ferris_stable.png")?;
-        let ferris_jumping_image = graphics::Image::new(ctx, "/ferris_jumping.png")?;
-        let enemy_image = graphics::Image::new(ctx, "/enemy.png")?;
-        let boost_life_image = graphics::Image::new(ctx, "/boost_life.png")?;
-        let boost_slow_down_image = graphics::Image::new(ctx, "/boost_slow-down.png")?;
-        let boost_speed_up_image = graphics::Image::new(ctx, "/boost_speed-up.png")?;
-        let pipe_top_image = graphics::Image::new(ctx, "/pipe-top.png")?;
-        let pipe_bottom_image = graphics::Image::new(ctx, "/pipe-bottom.png")?;
-        let background_image = graphics::Image::new(ctx, "/background.png")?;
-        let logo_start_screen_image = graphics::Image::new(ctx, "/logo_start_screen.png")?;
-        let logo_game_over_image = graphics::Image::new(ctx, "/logo_game_over.png")?;
-
-        let boost_sound = audio::Source::new(ctx, "/boost.ogg")?;
-        let death_sound = audio::Source::new(ctx, "/death.ogg")?;
-
-        Ok(Assets {
-            ferris_stable_image,
-            ferris_jumping_image,
-            enemy_image,
-            boost_life_image,
-            boost_slow_down_image,
-            boost_speed_up_image,
-            pipe_top_image,
-            pipe_bottom_image,
-            background_image,
-            logo_start_screen_image,
-            logo_game_over_image,
-
-            boost_sound,
-            death_sound,
-        })
+        let base = Assets::read_manifest(ctx, MANIFEST_PATH, "")?;
+        Assets::check_required(MANIFEST_PATH, &base)?;
+
+        let mut assets = Assets {
+            images: HashMap::new(),
+            sounds: HashMap::new(),
+            fonts: HashMap::new(),
+            animations: HashMap::new(),
+            colors: HashMap::new(),
+
+            base: base.clone(),
+            loaded: HashMap::new(),
+            theme: DEFAULT_THEME.to_string(),
+        };
+
+        assets.load_all(ctx, &base)?;
+
+        Ok(assets)
+    }
+
+    // Reads and parses a manifest. Relative paths in it are resolved from the given folder.
+    fn read_manifest(
+        ctx: &mut Context,
+        path: &str,
+        folder: &str,
+    ) -> GameResult<Vec<ManifestEntry>> {
+        let source = read_resource(ctx, path)?;
+        let mut entries = parse_manifest(&source).map_err(|errors| {
+            GameError::ResourceLoadError(format!(
+                "Invalid asset manifest {}:\n{}",
+                path,
+                errors.join("\n")
+            ))
+        })?;
+
+        for entry in entries.iter_mut() {
+            if entry.kind != AssetKind::Color && !entry.path.starts_with('/') {
+                entry.path = format!("{}/{}", folder, entry.path);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    // Makes sure nothing the game looks up is missing, so a broken manifest is reported before it is used
+    // instead of crashing the game later.
+    fn check_required(manifest: &str, entries: &[ManifestEntry]) -> GameResult {
+        let missing: Vec<String> = REQUIRED_ASSETS
+            .iter()
+            .filter(|(kind, id)| {
+                !entries
+                    .iter()
+                    .any(|entry| entry.kind == *kind && entry.id == *id)
+            })
+            .map(|(kind, id)| format!("{:?} '{}'", kind, id).to_lowercase())
+            .collect();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(GameError::ResourceLoadError(format!(
+                "Missing from the asset manifest {}: {}",
+                manifest,
+                missing.join(", ")
+            )))
+        }
+    }
+
+    // Returns the names of all available themes, starting with the default one.
+    pub fn themes(ctx: &mut Context) -> Vec<String> {
+        let mut themes: Vec<String> = match filesystem::read_dir(ctx, THEMES_PATH) {
+            Ok(folders) => folders
+                .filter_map(|folder| Some(folder.file_name()?.to_str()?.to_string()))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        themes.retain(|theme| {
+            filesystem::exists(ctx, format!("{}/{}/{}", THEMES_PATH, theme, THEME_MANIFEST))
+        });
+        themes.sort();
+        themes.dedup();
+        themes.insert(0, DEFAULT_THEME.to_string());
+
+        themes
+    }
+
+    // Applies a theme on top of the main manifest. Only the resources that differ from the loaded ones are loaded again.
+    // Returns the ids of everything that was loaded again.
+    pub fn set_theme(&mut self, ctx: &mut Context, theme: &str) -> GameResult<Vec<String>> {
+        let entries = Assets::theme_entries(ctx, &self.base, theme)?;
+        let reloaded = self.load_changed(ctx, entries)?;
+        self.theme = theme.to_string();
+
+        Ok(reloaded)
+    }
+
+    // The entries of the main manifest with the ones of the theme applied, checked before anything is loaded from them.
+    fn theme_entries(
+        ctx: &mut Context,
+        base: &[ManifestEntry],
+        theme: &str,
+    ) -> GameResult<Vec<ManifestEntry>> {
+        let mut entries = base.to_vec();
+
+        if theme != DEFAULT_THEME {
+            let folder = format!("{}/{}", THEMES_PATH, theme);
+            let overrides =
+                Assets::read_manifest(ctx, &format!("{}/{}", folder, THEME_MANIFEST), &folder)?;
+
+            for entry in overrides {
+                match entries
+                    .iter_mut()
+                    .find(|e| e.id == entry.id && e.kind == entry.kind)
+                {
+                    Some(existing) => *existing = entry,
+                    None => entries.push(entry),
+                }
+            }
+        }
+
+        let manifest = match theme {
+            DEFAULT_THEME => MANIFEST_PATH.to_string(),
+            _ => format!("{}/{}/{}", THEMES_PATH, theme, THEME_MANIFEST),
+        };
+        Assets::check_required(&manifest, &entries)?;
+
+        Ok(entries)
+    }
+
+    // Loads the entries that differ from the loaded ones and returns their ids.
+    fn load_changed(
+        &mut self,
+        ctx: &mut Context,
+        entries: Vec<ManifestEntry>,
+    ) -> GameResult<Vec<String>> {
+        let changed: Vec<ManifestEntry> = entries
+            .into_iter()
+            .filter(|entry| self.loaded.get(&entry.id) != Some(entry))
+            .collect();
+
+        self.load_all(ctx, &changed)?;
+
+        Ok(changed.into_iter().map(|entry| entry.id).collect())
+    }
+
+    // Loads again everything that uses one of the given files (paths inside the resources folder).
+    // If one of the used manifests changed, it is read again and only the entries that differ are loaded.
+    // Resources that fail to load keep their previous version. Returns the ids of everything that was loaded again.
+    pub fn reload(&mut self, ctx: &mut Context, paths: &[String]) -> GameResult<Vec<String>> {
+        let mut reloaded = Vec::new();
+
+        let theme_manifest = format!("{}/{}/{}", THEMES_PATH, self.theme, THEME_MANIFEST);
+        if paths
+            .iter()
+            .any(|path| path == MANIFEST_PATH || *path == theme_manifest)
+        {
+            // The changed manifests are only used once they are checked, so a broken edit keeps the previous ones.
+            let base = Assets::read_manifest(ctx, MANIFEST_PATH, "")?;
+            let entries = Assets::theme_entries(ctx, &base, &self.theme)?;
+
+            self.base = base;
+            reloaded = self.load_changed(ctx, entries)?;
+        }
+
+        let stale: Vec<ManifestEntry> = self
+            .loaded
+            .values()
+            .filter(|entry| entry.kind != AssetKind::Color && paths.contains(&entry.path))
+            .filter(|entry| !reloaded.contains(&entry.id))
+            .cloned()
+            .collect();
+
+        self.load_all(ctx, &stale)?;
+        reloaded.extend(stale.into_iter().map(|entry| entry.id));
+
+        Ok(reloaded)
+    }
+
+    // Every entry is loaded even if some of them fail, so all missing files are reported at once.
+    // The images are loaded first, so the animations can check that their sprite sheets are there.
+    fn load_all(&mut self, ctx: &mut Context, entries: &[ManifestEntry]) -> GameResult {
+        let mut entries: Vec<&ManifestEntry> = entries.iter().collect();
+        entries.sort_by_key(|entry| entry.kind != AssetKind::Image);
+
+        let errors: Vec<String> = entries
+            .into_iter()
+            .filter_map(|entry| self.load(ctx, entry).err())
+            .map(|error| error.to_string())
+            .collect();
+
+        if !errors.is_empty() {
+            return Err(GameError::ResourceLoadError(format!(
+                "Failed to load {} asset(s):\n{}",
+                errors.len(),
+                errors.join("\n")
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Loads a single entry, replacing the resource with the same id if there is one.
+    pub fn load(&mut self, ctx: &mut Context, entry: &ManifestEntry) -> GameResult {
+        let describe = |error: GameError| {
+            GameError::ResourceLoadError(format!("{} ({}): {}", entry.id, entry.path, error))
+        };
+
+        match entry.kind {
+            AssetKind::Image => {
+                let image = graphics::Image::new(ctx, &entry.path).map_err(describe)?;
+                self.images.insert(entry.id.clone(), image);
+            }
+            AssetKind::Sound => {
+                let sound = audio::Source::new(ctx, &entry.path).map_err(describe)?;
+                self.sounds.insert(entry.id.clone(), sound);
+            }
+            AssetKind::Font => {
+                let font = graphics::Font::new(ctx, &entry.path).map_err(describe)?;
+                self.fonts.insert(entry.id.clone(), font);
+            }
+            AssetKind::Animation => {
+                let source = read_resource(ctx, &entry.path).map_err(describe)?;
+                let set = animation::parse_animation(&source)
+                    .map_err(|errors| describe(GameError::ResourceLoadError(errors.join("; "))))?;
+                if !self.images.contains_key(&set.sheet) {
+                    return Err(describe(GameError::ResourceLoadError(format!(
+                        "no image with id '{}' for the sprite sheet",
+                        set.sheet
+                    ))));
+                }
+                self.animations.insert(entry.id.clone(), set);
+            }
+            AssetKind::Color => {
+                let color = parse_color(&entry.path).ok_or_else(|| {
+                    describe(GameError::ResourceLoadError("invalid color".to_string()))
+                })?;
+                self.colors.insert(entry.id.clone(), color);
+            }
+        };
+
+        self.loaded.insert(entry.id.clone(), entry.clone());
+
+        Ok(())
+    }
+
+    // Every id the game looks up is checked with REQUIRED_ASSETS when a manifest is loaded,
+    // so a missing one here is a bug in the game and not in the manifest.
+    pub fn image(&self, id: &str) -> &graphics::Image {
+        self.images
+            .get(id)
+            .unwrap_or_else(|| panic!("No image with id '{}' in the asset manifest", id))
+    }
+
+    pub fn sound(&mut self, id: &str) -> &mut audio::Source {
+        self.sounds
+            .get_mut(id)
+            .unwrap_or_else(|| panic!("No sound with id '{}' in the asset manifest", id))
+    }
+
+    // A language can name a font that is not in the manifest, then the main one is used instead.
+    pub fn font(&self, id: &str) -> graphics::Font {
+        match self.fonts.get(id) {
+            Some(font) => *font,
+            None => self.fonts["main"],
+        }
+    }
+
+    pub fn animation(&self, id: &str) -> &AnimationSet {
+        self.animations
+            .get(id)
+            .unwrap_or_else(|| panic!("No animation with id '{}' in the asset manifest", id))
+    }
+
+    pub fn color(&self, id: &str) -> graphics::Color {
+        *self
+            .colors
+            .get(id)
+            .unwrap_or_else(|| panic!("No color with id '{}' in the asset manifest", id))
     }
 }
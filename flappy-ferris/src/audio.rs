@@ -0,0 +1,204 @@
+use crate::assets::Assets;
+use crate::events::GameEvent;
+use ggez::audio::{self, SoundSource};
+use ggez::{Context, GameResult};
+use rand::Rng;
+
+// Used for changing volumes and fading the music.
+pub const VOLUME_STEP: f32 = 0.1;
+pub const FADE_TIME: f32 = 1.0;
+pub const DUCK_LEVEL: f32 = 0.3;
+pub const DUCK_TIME: f32 = 1.5;
+// How much the pitch of frequent sounds (like flapping) can differ, so they don't sound the same every time.
+pub const PITCH_VARIATION: f32 = 0.08;
+
+// The looping background tracks.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MusicTrack {
+    Menu,
+    Gameplay,
+}
+impl MusicTrack {
+    // The id of the track in the asset manifest.
+    pub fn id(&self) -> &'static str {
+        match self {
+            MusicTrack::Menu => "music_menu",
+            MusicTrack::Gameplay => "music_gameplay",
+        }
+    }
+}
+
+// The volume channels. Music and SFX are both scaled by the master volume.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Channel {
+    Master,
+    Music,
+    Sfx,
+}
+
+// Controls the volumes of everything that is played and the fading between music tracks.
+pub struct Mixer {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub muted: bool,
+
+    // The track that is playing at the moment and the one that should play after the fade out.
+    current: Option<MusicTrack>,
+    requested: Option<MusicTrack>,
+    // Goes from 0.0 to 1.0 while fading in and back to 0.0 while fading out.
+    fade: f32,
+    // Seconds left until the music stops being quieter because of a loud sound effect.
+    duck_time: f32,
+}
+impl Mixer {
+    pub fn new() -> Self {
+        Self {
+            master: 1.0,
+            music: 0.6,
+            sfx: 1.0,
+            muted: false,
+
+            current: None,
+            requested: None,
+            fade: 0.0,
+            duck_time: 0.0,
+        }
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    // Changes the volume of a channel by the given amount, keeping it between 0.0 and 1.0.
+    pub fn change_volume(&mut self, channel: Channel, amount: f32) {
+        let volume = match channel {
+            Channel::Master => &mut self.master,
+            Channel::Music => &mut self.music,
+            Channel::Sfx => &mut self.sfx,
+        };
+
+        *volume = (*volume + amount).clamp(0.0, 1.0);
+    }
+
+    // The final volume of a channel after the master volume and muting are applied.
+    pub fn volume(&self, channel: Channel) -> f32 {
+        if self.muted {
+            return 0.0;
+        }
+
+        match channel {
+            Channel::Master => self.master,
+            Channel::Music => self.master * self.music,
+            Channel::Sfx => self.master * self.sfx,
+        }
+    }
+
+    // Starts fading to the given track. Calling it with the track that is already playing does nothing.
+    pub fn play_music(&mut self, track: MusicTrack) {
+        self.requested = Some(track);
+    }
+
+    // Must be called when assets are loaded again, since the playing track could have been replaced (and stopped).
+    pub fn assets_reloaded(&mut self, ids: &[String]) {
+        if let Some(track) = self.current {
+            if ids.iter().any(|id| id == track.id()) {
+                self.current = None;
+                self.fade = 0.0;
+            }
+        }
+    }
+
+    // Makes the music quieter for a while, so a sound effect can be heard clearly.
+    pub fn duck(&mut self) {
+        self.duck_time = DUCK_TIME;
+    }
+
+    // Plays a one-shot sound effect with the SFX volume.
+    pub fn play_sfx(&self, ctx: &mut Context, sound: &mut audio::Source) -> GameResult {
+        sound.set_pitch(1.0);
+        sound.set_volume(self.volume(Channel::Sfx));
+        sound.play_detached(ctx)
+    }
+
+    // Same as play_sfx, but with a slightly random pitch.
+    pub fn play_sfx_varied(&self, ctx: &mut Context, sound: &mut audio::Source) -> GameResult {
+        let pitch = rand::thread_rng().gen_range((1.0 - PITCH_VARIATION)..(1.0 + PITCH_VARIATION));
+
+        sound.set_pitch(pitch);
+        sound.set_volume(self.volume(Channel::Sfx));
+        sound.play_detached(ctx)
+    }
+
+    // Plays the sound that belongs to the given game event.
+    pub fn play_event(
+        &mut self,
+        ctx: &mut Context,
+        assets: &mut Assets,
+        event: GameEvent,
+    ) -> GameResult {
+        match event {
+            GameEvent::Flap => self.play_sfx_varied(ctx, assets.sound("flap")),
+            GameEvent::PipePassed { .. } => self.play_sfx_varied(ctx, assets.sound("score")),
+            GameEvent::LifeLost => self.play_sfx(ctx, assets.sound("life_lost")),
+            GameEvent::EnemyPassed => self.play_sfx_varied(ctx, assets.sound("enemy_pass")),
+            GameEvent::BoostCollected => self.play_sfx(ctx, assets.sound("boost")),
+            GameEvent::BoostExpired => self.play_sfx(ctx, assets.sound("boost_end")),
+            GameEvent::Death => {
+                self.duck();
+                self.play_sfx(ctx, assets.sound("death"))
+            }
+            GameEvent::MenuMove => self.play_sfx_varied(ctx, assets.sound("menu_move")),
+            GameEvent::MenuSelect => self.play_sfx(ctx, assets.sound("menu_select")),
+        }
+    }
+
+    // Called every frame with its length in seconds. Handles fading, ducking and the music volume.
+    pub fn update(&mut self, ctx: &mut Context, assets: &mut Assets, delta: f32) -> GameResult {
+        let step = delta / FADE_TIME;
+
+        if self.current != self.requested {
+            self.fade -= step;
+
+            // The old track is quiet enough, so it is replaced with the requested one.
+            if self.fade <= 0.0 || self.current.is_none() {
+                if let Some(track) = self.current {
+                    Mixer::music_source(assets, track).stop(ctx)?;
+                }
+
+                self.fade = 0.0;
+                self.current = self.requested;
+
+                if let Some(track) = self.current {
+                    let source = Mixer::music_source(assets, track);
+                    source.set_repeat(true);
+                    source.set_volume(0.0);
+                    source.play(ctx)?;
+                }
+            }
+        } else {
+            self.fade = (self.fade + step).min(1.0);
+        }
+
+        if self.duck_time > 0.0 {
+            self.duck_time -= delta;
+        }
+
+        let duck = if self.duck_time > 0.0 {
+            DUCK_LEVEL
+        } else {
+            1.0
+        };
+
+        if let Some(track) = self.current {
+            let volume = self.volume(Channel::Music) * self.fade * duck;
+            Mixer::music_source(assets, track).set_volume(volume);
+        }
+
+        Ok(())
+    }
+
+    fn music_source(assets: &mut Assets, track: MusicTrack) -> &mut audio::Source {
+        assets.sound(track.id())
+    }
+}
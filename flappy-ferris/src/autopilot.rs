@@ -0,0 +1,211 @@
+use crate::entities::{
+    BoostType, PlayerEntity, BOOST_SPEED, ENEMY_SPEED, FERRIS_HEIGHT, FLOOR_LEVEL, GRAVITY, JUMP,
+    MIDDLE, PIPE_SPEED,
+};
+use crate::sim::Simulation;
+use ggez::graphics::Rect;
+use std::collections::HashMap;
+
+// Used for planning the flight: how many ticks ahead the autopilot looks and how often it considers flapping on the way.
+// Flapping only every few ticks keeps the search small (a flap needs a tick with the input released before it anyway).
+pub const PLAN_TICKS: usize = 90;
+pub const FLAP_INTERVAL: usize = 4;
+// Obstacles are treated as this much bigger, so rounding and the ones still off screen do not surprise it.
+pub const SAFETY_MARGIN: f32 = 1.0;
+// The height around the target the autopilot is happy to fall through before flapping.
+pub const TARGET_SLACK: f32 = 12.0;
+// Boosts closer than this are aimed for (besides speed-ups, which are avoided like enemies).
+pub const BOOST_REACH: f32 = 400.0;
+
+// Where Ferris is and how fast it is falling, as the player entity moves it.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Arc {
+    y: f32,
+    velocity: f32,
+}
+impl Arc {
+    // The same as PlayerEntity::update: a flap sets the velocity, gravity is added and Ferris can not go over the top.
+    fn next(&self, flap: bool) -> Self {
+        let velocity = if flap { -JUMP } else { self.velocity } + GRAVITY;
+        let y = (self.y + velocity).max(FERRIS_HEIGHT / 2.0);
+
+        Self { y, velocity }
+    }
+
+    // Heights and velocities are always multiples of half a pixel, so they make an exact key for the states already tried.
+    fn key(&self, tick: usize) -> (usize, i32, i32) {
+        (
+            tick,
+            (self.y * 2.0).round() as i32,
+            (self.velocity * 2.0).round() as i32,
+        )
+    }
+}
+
+// Something Ferris should not touch, moving left by the same amount every tick.
+struct Obstacle {
+    zone: Rect,
+    speed: f32,
+}
+
+// A computer player. It predicts where the pipes, enemies and boosts will be and searches for flaps that get
+// Ferris through the next PLAN_TICKS without touching any of them (or the ground), aiming for the middle of the next gap.
+pub struct Autopilot {
+    pub plan_ticks: usize,
+    pub flap_interval: usize,
+    pub safety_margin: f32,
+}
+impl Autopilot {
+    pub fn new() -> Self {
+        Self {
+            plan_ticks: PLAN_TICKS,
+            flap_interval: FLAP_INTERVAL,
+            safety_margin: SAFETY_MARGIN,
+        }
+    }
+
+    // Returns if the player should hold the flap input down during this tick.
+    pub fn decide(&self, player: &PlayerEntity, sim: &Simulation) -> bool {
+        // The flap is edge-triggered, so the input has to be released between flaps.
+        if !player.can_jump {
+            return false;
+        }
+
+        let obstacles = self.obstacles(sim);
+        let now = Arc {
+            y: player.position.y,
+            velocity: player.physics.velocity,
+        };
+        let plan = Plan {
+            autopilot: self,
+            player: player.zone,
+            obstacles: &obstacles,
+        };
+
+        // When there is no way through, the one that stays out of trouble for longer is taken.
+        let flap = plan.reach(now.next(true), 1, &mut HashMap::new());
+        let fall = plan.reach(now.next(false), 1, &mut HashMap::new());
+        let below_target = now.y > self.target(player, sim) + TARGET_SLACK;
+
+        if flap != fall {
+            flap > fall
+        } else {
+            below_target && now.velocity >= 0.0
+        }
+    }
+
+    // The height to fly at: the middle of the next gap, a boost worth taking or the middle of the screen.
+    fn target(&self, player: &PlayerEntity, sim: &Simulation) -> f32 {
+        let left = player.zone.x;
+        let next_pipe = sim
+            .pipes
+            .iter()
+            .filter(|pipe| pipe.top_zone.x + pipe.top_zone.w > left)
+            .min_by(|a, b| a.position.x.partial_cmp(&b.position.x).unwrap());
+        let next_boost = sim.boosts.iter().find(|boost| {
+            boost.effect != BoostType::SpeedUp
+                && boost.zone.x + boost.zone.w > left
+                && boost.position.x - player.position.x < BOOST_REACH
+        });
+
+        match (next_pipe, next_boost) {
+            // A boost before the next pipe is taken first.
+            (Some(pipe), Some(boost)) if boost.position.x < pipe.position.x => boost.position.y,
+            (Some(pipe), _) => pipe.top_zone.h + (pipe.bottom_zone.y - pipe.top_zone.h) / 2.0,
+            (None, Some(boost)) => boost.position.y,
+            (None, None) => MIDDLE,
+        }
+    }
+
+    fn obstacles(&self, sim: &Simulation) -> Vec<Obstacle> {
+        let grow = |zone: Rect| Rect {
+            x: zone.x - self.safety_margin,
+            y: zone.y - self.safety_margin,
+            w: zone.w + self.safety_margin * 2.0,
+            h: zone.h + self.safety_margin * 2.0,
+        };
+
+        let mut obstacles = Vec::new();
+        for pipe in sim.pipes.iter() {
+            for zone in [pipe.top_zone, pipe.bottom_zone] {
+                obstacles.push(Obstacle {
+                    zone: grow(zone),
+                    speed: PIPE_SPEED * sim.multiplier,
+                });
+            }
+        }
+        for enemy in sim.enemies.iter() {
+            obstacles.push(Obstacle {
+                zone: grow(enemy.zone),
+                speed: ENEMY_SPEED * sim.multiplier,
+            });
+        }
+        // A speed-up makes everything harder, so it is better left alone.
+        for boost in sim
+            .boosts
+            .iter()
+            .filter(|boost| boost.effect == BoostType::SpeedUp)
+        {
+            obstacles.push(Obstacle {
+                zone: grow(boost.zone),
+                speed: BOOST_SPEED,
+            });
+        }
+
+        obstacles
+    }
+}
+
+// A search for a safe way through the obstacles from one state of Ferris.
+struct Plan<'a> {
+    autopilot: &'a Autopilot,
+    player: Rect,
+    obstacles: &'a [Obstacle],
+}
+impl<'a> Plan<'a> {
+    fn hits(&self, arc: Arc, tick: usize) -> bool {
+        if arc.y > FLOOR_LEVEL - self.autopilot.safety_margin {
+            return true;
+        }
+
+        let zone = Rect {
+            y: arc.y - self.player.h / 2.0,
+            ..self.player
+        };
+        self.obstacles.iter().any(|obstacle| {
+            let moved = Rect {
+                x: obstacle.zone.x - obstacle.speed * tick as f32,
+                ..obstacle.zone
+            };
+            moved.overlaps(&zone)
+        })
+    }
+
+    // The last tick Ferris can get to without hitting anything (plan_ticks if it gets through).
+    // Falling is tried before flapping, so the plan found flaps as little as it can.
+    // The states already searched are remembered, so the same ones are not searched again.
+    fn reach(
+        &self,
+        arc: Arc,
+        tick: usize,
+        reached: &mut HashMap<(usize, i32, i32), usize>,
+    ) -> usize {
+        if self.hits(arc, tick) {
+            return tick - 1;
+        }
+        if tick >= self.autopilot.plan_ticks {
+            return tick;
+        }
+        if let Some(&reach) = reached.get(&arc.key(tick)) {
+            return reach;
+        }
+
+        let mut reach = self.reach(arc.next(false), tick + 1, reached);
+        if reach < self.autopilot.plan_ticks && tick % self.autopilot.flap_interval == 0 {
+            reach = reach.max(self.reach(arc.next(true), tick + 1, reached));
+        }
+        reached.insert(arc.key(tick), reach);
+
+        reach
+    }
+}
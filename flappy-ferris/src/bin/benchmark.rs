@@ -0,0 +1,102 @@
+// Checks that the generated courses can be beaten. The autopilot plays a game on each of many seeds without a window
+// and the seeds it could not get through are listed, so they can be looked at with `cargo run -- --seed <seed>`.
+//
+// Usage: cargo run --release --bin benchmark -- [courses] [seconds per course] [first seed]
+use flappy_ferris::autopilot::Autopilot;
+use flappy_ferris::entities::PlayState;
+use flappy_ferris::events::GameEvent;
+use flappy_ferris::players::GameMode;
+use flappy_ferris::sim::{Simulation, TICKS_PER_SECOND};
+use std::time::Instant;
+
+// Used when the options are not given or are not numbers. A course counts as beaten if the autopilot is still flying after that many seconds.
+pub const DEFAULT_COURSES: u64 = 100;
+pub const DEFAULT_SECONDS: u64 = 120;
+
+// How a game on one course went.
+struct Run {
+    seed: u64,
+    score: i128,
+    ticks: u64,
+    lifes_lost: usize,
+    beaten: bool,
+}
+
+fn play(autopilot: &Autopilot, seed: u64, max_ticks: u64) -> Run {
+    let mut sim = Simulation::new(GameMode::Single);
+    sim.seed(seed);
+    sim.play_state = PlayState::Play;
+
+    let mut events = Vec::new();
+    let mut lifes_lost = 0;
+    let mut ticks = 0;
+    let mut over = false;
+    while ticks < max_ticks && !over {
+        let flap = autopilot.decide(&sim.players[0].entity, &sim);
+        over = sim.step(&[flap], &mut events).over;
+        ticks += 1;
+
+        lifes_lost += events
+            .drain(..)
+            .filter(|event| *event == GameEvent::LifeLost)
+            .count();
+    }
+
+    Run {
+        seed,
+        score: sim.teams[0].score,
+        ticks,
+        lifes_lost,
+        beaten: !over,
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut option = |default: u64| {
+        args.next()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    };
+    let courses = option(DEFAULT_COURSES);
+    let seconds = option(DEFAULT_SECONDS);
+    let first_seed = option(0);
+
+    let autopilot = Autopilot::new();
+    let max_ticks = seconds * TICKS_PER_SECOND as u64;
+    let started = Instant::now();
+
+    let runs: Vec<Run> = (first_seed..first_seed + courses)
+        .map(|seed| play(&autopilot, seed, max_ticks))
+        .collect();
+
+    for run in runs.iter().filter(|run| !run.beaten || run.lifes_lost > 0) {
+        println!(
+            "Seed {}: {} after {:.1} s with {} points ({} lifes lost)",
+            run.seed,
+            if run.beaten { "survived" } else { "crashed" },
+            run.ticks as f32 / TICKS_PER_SECOND as f32,
+            run.score,
+            run.lifes_lost
+        );
+    }
+
+    let beaten = runs.iter().filter(|run| run.beaten).count();
+    let ticks: u64 = runs.iter().map(|run| run.ticks).sum();
+    let score: i128 = runs.iter().map(|run| run.score).sum();
+    let elapsed = started.elapsed().as_secs_f64();
+
+    println!(
+        "Beaten {} of {} courses ({} s each), {:.1} points on average",
+        beaten,
+        runs.len(),
+        seconds,
+        score as f64 / runs.len().max(1) as f64
+    );
+    println!(
+        "Simulated {} ticks in {:.2} s ({:.3} ms per tick)",
+        ticks,
+        elapsed,
+        elapsed * 1000.0 / ticks.max(1) as f64
+    );
+}
@@ -0,0 +1,414 @@
+// The lobby server of online races. Players who join are put in the lobby and once enough of them are ready,
+// they all get the same seed and race on the same course. The server only passes the inputs of each player
+// to the others and collects the scores at the end, the games themselves run on the clients.
+//
+// Usage: cargo run --bin lobby -- [address] [players needed for a race]
+use flappy_ferris::net::{
+    Message, CLIENT_TIMEOUT, DEFAULT_PORT, MAX_MESSAGE_SIZE, RESEND_INTERVAL,
+};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+// Used for starting the races. After a race ends the players get a few seconds to look at the results.
+pub const DEFAULT_PLAYERS_NEEDED: usize = 2;
+pub const RACE_COOLDOWN: f32 = 3.0;
+// How long the server waits for a message before checking the timeouts and the race.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+struct Client {
+    id: u32,
+    name: String,
+    address: SocketAddr,
+    last_seen: Instant,
+    // In the lobby and ready for the next race.
+    ready: bool,
+    // In the current race, with their score after crashing.
+    racing: bool,
+    // Set once an input from them shows that the start of the race arrived.
+    started: bool,
+    score: Option<i128>,
+}
+
+struct Race {
+    seed: u64,
+    players: Vec<(u32, String)>,
+    last_start: Instant,
+}
+
+struct Lobby {
+    socket: UdpSocket,
+    clients: Vec<Client>,
+    next_id: u32,
+    players_needed: usize,
+    race: Option<Race>,
+    // The players and results of the last race, sent again to anyone who missed them.
+    last_players: Vec<u32>,
+    last_results: Vec<(String, i128)>,
+    next_race: Instant,
+}
+impl Lobby {
+    fn new(address: &str, players_needed: usize) -> io::Result<Self> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        Ok(Self {
+            socket,
+            clients: Vec::new(),
+            next_id: 1,
+            players_needed: players_needed.max(1),
+            race: None,
+            last_players: Vec::new(),
+            last_results: Vec::new(),
+            next_race: Instant::now(),
+        })
+    }
+
+    fn run(&mut self) {
+        loop {
+            self.update();
+        }
+    }
+
+    // Handles the next message (waiting for it up to POLL_INTERVAL), the clients that went silent and the race.
+    fn update(&mut self) {
+        let mut buffer = [0; MAX_MESSAGE_SIZE];
+
+        match self.socket.recv_from(&mut buffer) {
+            Ok((size, address)) => {
+                if let Some(message) = std::str::from_utf8(&buffer[..size])
+                    .ok()
+                    .and_then(Message::parse)
+                {
+                    self.receive(message, address);
+                }
+            }
+            Err(error)
+                if error.kind() == io::ErrorKind::WouldBlock
+                    || error.kind() == io::ErrorKind::TimedOut => {}
+            // A client that closed its port shows up as an error on some systems, the server keeps going.
+            Err(error) => eprintln!("Could not receive: {}", error),
+        }
+
+        self.drop_silent_clients();
+        self.update_race();
+    }
+
+    fn receive(&mut self, message: Message, address: SocketAddr) {
+        if let Message::Join { name } = message {
+            self.join(name, address);
+            return;
+        }
+
+        // All other messages must come from the client with that id.
+        let index = match self
+            .clients
+            .iter()
+            .position(|client| client.address == address)
+        {
+            Some(index) => index,
+            None => return,
+        };
+        self.clients[index].last_seen = Instant::now();
+
+        match message {
+            Message::Input { id, .. } if id == self.clients[index].id => {
+                if self.clients[index].racing {
+                    self.clients[index].started = true;
+                    self.relay(&message, id);
+                }
+            }
+            Message::Result { id, score } if id == self.clients[index].id => {
+                if self.clients[index].racing {
+                    if self.clients[index].score.is_none() {
+                        self.clients[index].score = Some(score);
+                        self.relay(&message, id);
+                    }
+                } else if self.last_players.contains(&id) {
+                    self.send(
+                        &Message::Results {
+                            scores: self.last_results.clone(),
+                        },
+                        address,
+                    );
+                }
+            }
+            Message::Leave { id } if id == self.clients[index].id => {
+                let client = self.clients.remove(index);
+                println!("{} left", client.name);
+            }
+            _ => (),
+        }
+    }
+
+    // Adds a new player to the lobby. A player already in it is only marked as ready again.
+    fn join(&mut self, name: String, address: SocketAddr) {
+        let index = match self
+            .clients
+            .iter()
+            .position(|client| client.address == address)
+        {
+            Some(index) => index,
+            None => {
+                self.clients.push(Client {
+                    id: self.next_id,
+                    name: name.clone(),
+                    address,
+                    last_seen: Instant::now(),
+                    ready: false,
+                    racing: false,
+                    started: false,
+                    score: None,
+                });
+                self.next_id += 1;
+                println!("{} joined from {}", name, address);
+
+                self.clients.len() - 1
+            }
+        };
+
+        let client = &mut self.clients[index];
+        client.last_seen = Instant::now();
+        client.name = name;
+        if !client.racing {
+            client.ready = true;
+        }
+
+        let id = client.id;
+        self.send(&Message::Welcome { id }, address);
+    }
+
+    fn drop_silent_clients(&mut self) {
+        let timeout = Duration::from_secs_f32(CLIENT_TIMEOUT);
+        self.clients.retain(|client| {
+            let silent = client.last_seen.elapsed() > timeout;
+            if silent {
+                println!("{} timed out", client.name);
+            }
+
+            !silent
+        });
+    }
+
+    // Starts a race when enough players are ready and ends it once every player crashed (or left).
+    fn update_race(&mut self) {
+        let race = match self.race.as_mut() {
+            Some(race) => race,
+            None => {
+                let ready = self.clients.iter().filter(|client| client.ready).count();
+                if ready >= self.players_needed && Instant::now() >= self.next_race {
+                    self.start_race();
+                }
+                return;
+            }
+        };
+
+        // The start is sent again to everyone it may not have reached.
+        if race.last_start.elapsed().as_secs_f32() >= RESEND_INTERVAL {
+            race.last_start = Instant::now();
+
+            let start = Message::Start {
+                seed: race.seed,
+                players: race.players.clone(),
+            };
+            for client in self
+                .clients
+                .iter()
+                .filter(|client| client.racing && !client.started)
+            {
+                self.send(&start, client.address);
+            }
+        }
+
+        let racing: Vec<&Client> = self.clients.iter().filter(|client| client.racing).collect();
+        if racing.iter().all(|client| client.score.is_some()) {
+            let mut scores: Vec<(String, i128)> = racing
+                .iter()
+                .map(|client| (client.name.clone(), client.score.unwrap()))
+                .collect();
+            scores.sort_by(|a, b| b.1.cmp(&a.1));
+            self.end_race(scores);
+        }
+    }
+
+    fn start_race(&mut self) {
+        let seed = rand::random();
+        let mut players = Vec::new();
+
+        for client in self.clients.iter_mut().filter(|client| client.ready) {
+            client.ready = false;
+            client.racing = true;
+            client.started = false;
+            client.score = None;
+            players.push((client.id, client.name.clone()));
+        }
+
+        let names: Vec<&str> = players.iter().map(|(_, name)| name.as_str()).collect();
+        println!("Race {} started: {}", seed, names.join(", "));
+
+        // The start is sent on the next update, as if it was sent again.
+        self.race = Some(Race {
+            seed,
+            players,
+            last_start: Instant::now() - Duration::from_secs_f32(RESEND_INTERVAL),
+        });
+    }
+
+    fn end_race(&mut self, scores: Vec<(String, i128)>) {
+        println!("Race ended:");
+        for (place, (name, score)) in scores.iter().enumerate() {
+            println!("  {}. {} {}", place + 1, name, score);
+        }
+
+        let results = Message::Results {
+            scores: scores.clone(),
+        };
+        self.last_players.clear();
+        for client in self.clients.iter_mut().filter(|client| client.racing) {
+            client.racing = false;
+            self.last_players.push(client.id);
+        }
+        for &id in self.last_players.iter() {
+            if let Some(client) = self.clients.iter().find(|client| client.id == id) {
+                self.send(&results, client.address);
+            }
+        }
+
+        self.last_results = scores;
+        self.race = None;
+        self.next_race = Instant::now() + Duration::from_secs_f32(RACE_COOLDOWN);
+    }
+
+    // Passes a message of a player to everyone else in the race.
+    fn relay(&self, message: &Message, from: u32) {
+        for client in self
+            .clients
+            .iter()
+            .filter(|client| client.racing && client.id != from)
+        {
+            self.send(message, client.address);
+        }
+    }
+
+    fn send(&self, message: &Message, address: SocketAddr) {
+        if let Err(error) = self.socket.send_to(message.encode().as_bytes(), address) {
+            eprintln!("Could not send to {}: {}", address, error);
+        }
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let address = args
+        .next()
+        .unwrap_or_else(|| format!("0.0.0.0:{}", DEFAULT_PORT));
+    let players_needed = args
+        .next()
+        .and_then(|players| players.parse().ok())
+        .unwrap_or(DEFAULT_PLAYERS_NEEDED);
+
+    let mut lobby = Lobby::new(&address, players_needed).unwrap();
+    println!(
+        "Lobby listening on {}, a race starts with {} players",
+        address, lobby.players_needed
+    );
+
+    lobby.run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flappy_ferris::net::{NetClient, NetEvent, NetState};
+
+    // Runs the lobby and the clients until every client got an event the check accepts (or a few seconds passed).
+    fn run_until(
+        lobby: &mut Lobby,
+        clients: &mut [NetClient],
+        check: impl Fn(&NetEvent) -> bool,
+    ) -> Vec<NetEvent> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut found: Vec<Option<NetEvent>> = vec![None; clients.len()];
+
+        while found.iter().any(|event| event.is_none()) {
+            assert!(Instant::now() < deadline, "timed out, got {:?}", found);
+
+            lobby.update();
+            for (client, found) in clients.iter_mut().zip(found.iter_mut()) {
+                for event in client.update(POLL_INTERVAL.as_secs_f32()) {
+                    if found.is_none() && check(&event) {
+                        *found = Some(event);
+                    }
+                }
+            }
+        }
+
+        found.into_iter().flatten().collect()
+    }
+
+    #[test]
+    fn two_players_race_on_localhost() {
+        let mut lobby = Lobby::new("127.0.0.1:0", 2).unwrap();
+        let address = lobby.socket.local_addr().unwrap().to_string();
+        let mut clients = vec![
+            NetClient::connect(&address, "Ann Lee").unwrap(),
+            NetClient::connect(&address, "bob").unwrap(),
+        ];
+
+        // Both join and get the same race.
+        let started = run_until(&mut lobby, &mut clients, |event| {
+            matches!(event, NetEvent::Started { .. })
+        });
+        assert_eq!(started[0], started[1]);
+        let ids: Vec<u32> = clients.iter().map(|client| client.id.unwrap()).collect();
+        match &started[0] {
+            NetEvent::Started { players, .. } => assert_eq!(
+                players,
+                &vec![(ids[0], "Ann_Lee".to_string()), (ids[1], "bob".to_string())]
+            ),
+            _ => unreachable!(),
+        }
+        assert!(clients
+            .iter()
+            .all(|client| client.state == NetState::Racing));
+
+        // The flaps of one player reach the other one.
+        clients[0].send_input(0, true, 300.0);
+        clients[1].send_input(0, false, 310.0);
+        let inputs = run_until(&mut lobby, &mut clients, |event| {
+            matches!(event, NetEvent::Input { .. })
+        });
+        assert_eq!(
+            inputs[0],
+            NetEvent::Input {
+                id: ids[1],
+                first_tick: 0,
+                y: 310.0,
+                flaps: vec![false],
+            }
+        );
+        assert_eq!(
+            inputs[1],
+            NetEvent::Input {
+                id: ids[0],
+                first_tick: 0,
+                y: 300.0,
+                flaps: vec![true],
+            }
+        );
+
+        // Once both crashed, everyone gets the results sorted from the best score.
+        clients[0].finish(3);
+        clients[1].finish(5);
+        let results = run_until(&mut lobby, &mut clients, |event| {
+            matches!(event, NetEvent::Results { .. })
+        });
+        let expected = NetEvent::Results {
+            scores: vec![("bob".to_string(), 5), ("Ann_Lee".to_string(), 3)],
+        };
+        assert_eq!(results, vec![expected.clone(), expected]);
+        assert!(clients
+            .iter()
+            .all(|client| client.state == NetState::Waiting));
+    }
+}
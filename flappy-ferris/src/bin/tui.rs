@@ -0,0 +1,400 @@
+// Plays the game in a terminal, for example over SSH or on a computer without a GPU. The world is drawn with Unicode
+// half blocks, so every character cell shows two pixels, and it runs the same Simulation as the window does,
+// so only the drawing and the keys are different. Terminals do not tell when a key is released, so a key press
+// counts as holding the flap down for a single tick.
+//
+// Usage: cargo run --bin tui -- [--seed <number>] [--lang <language>]
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{
+    self, disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+use flappy_ferris::autopilot::Autopilot;
+use flappy_ferris::entities::{PlayState, FLOOR_LEVEL, SCREEN_HEIGHT, SCREEN_WIDTH};
+use flappy_ferris::events::GameEvent;
+use flappy_ferris::locale::{Locale, DEFAULT_LANGUAGE};
+use flappy_ferris::players::GameMode;
+use flappy_ferris::sim::{Simulation, TICK_NANOS};
+use ggez::graphics::Rect;
+use std::io::{self, Stdout, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Used for drawing the screen only every few ticks, which keeps the output small enough for slow connections.
+pub const TICKS_PER_FRAME: u64 = 2;
+// If the game falls behind by more than that many ticks (e.g. while the terminal is busy), it skips them.
+pub const MAX_TICKS_BEHIND: u32 = 15;
+
+// Used for the smallest terminal the game can be played in (in characters).
+pub const MIN_COLUMNS: u16 = 40;
+pub const MIN_ROWS: u16 = 15;
+
+// Used for the colors of the world, close to the ones of the classic theme.
+const SKY: Color = Color::Rgb {
+    r: 113,
+    g: 197,
+    b: 207,
+};
+const GROUND: Color = Color::Rgb {
+    r: 222,
+    g: 216,
+    b: 149,
+};
+const PIPE: Color = Color::Rgb {
+    r: 115,
+    g: 191,
+    b: 46,
+};
+const ENEMY: Color = Color::Rgb {
+    r: 70,
+    g: 60,
+    b: 90,
+};
+const FERRIS: Color = Color::Rgb {
+    r: 247,
+    g: 76,
+    b: 0,
+};
+// Players who are down (or crashed) are greyed out.
+const FERRIS_DOWN: Color = Color::Rgb {
+    r: 130,
+    g: 110,
+    b: 100,
+};
+const TEXT: Color = Color::Rgb {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+const TEXT_BACKGROUND: Color = Color::Rgb { r: 0, g: 0, b: 0 };
+
+// The screen in pixels (two for every character cell, one above the other) with the texts written over it.
+struct Canvas {
+    columns: usize,
+    rows: usize,
+    pixels: Vec<Color>,
+    letters: Vec<Option<char>>,
+}
+impl Canvas {
+    fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            columns,
+            rows,
+            pixels: vec![SKY; columns * rows * 2],
+            letters: vec![None; columns * rows],
+        }
+    }
+
+    // Paints a rectangle given in the coordinates of the game screen, which is stretched over the whole terminal.
+    // Everything on the screen is at least one pixel big, so even small things do not disappear.
+    fn fill(&mut self, zone: &Rect, color: Color) {
+        let scale_x = self.columns as f32 / SCREEN_WIDTH;
+        let scale_y = (self.rows * 2) as f32 / SCREEN_HEIGHT;
+        let span = |start: f32, end: f32, size: usize| {
+            let first = (start.round().max(0.0) as usize).min(size);
+            let last = (end.round().max(0.0) as usize).min(size);
+            if first == last && first < size && end > 0.0 {
+                (first, first + 1)
+            } else {
+                (first, last)
+            }
+        };
+
+        let (left, right) = span(zone.x * scale_x, (zone.x + zone.w) * scale_x, self.columns);
+        let (top, bottom) = span(zone.y * scale_y, (zone.y + zone.h) * scale_y, self.rows * 2);
+        for y in top..bottom {
+            for x in left..right {
+                self.pixels[y * self.columns + x] = color;
+            }
+        }
+    }
+
+    // Writes a text at the given cell, cutting off what does not fit.
+    fn write(&mut self, row: usize, column: usize, text: &str) {
+        if row >= self.rows {
+            return;
+        }
+        for (offset, letter) in text.chars().enumerate() {
+            if column + offset >= self.columns {
+                break;
+            }
+            self.letters[row * self.columns + column + offset] = Some(letter);
+        }
+    }
+
+    fn write_centered(&mut self, row: usize, text: &str) {
+        let column = self.columns.saturating_sub(text.chars().count()) / 2;
+        self.write(row, column, text);
+    }
+
+    fn write_right(&mut self, row: usize, text: &str) {
+        let column = self.columns.saturating_sub(text.chars().count());
+        self.write(row, column, text);
+    }
+
+    // The whole screen as terminal output. The colors are only sent when they change.
+    fn render(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut colors = None;
+        for row in 0..self.rows {
+            queue!(out, MoveTo(0, row as u16))?;
+            for column in 0..self.columns {
+                let (symbol, foreground, background) =
+                    match self.letters[row * self.columns + column] {
+                        Some(letter) => (letter, TEXT, TEXT_BACKGROUND),
+                        None => (
+                            '▀',
+                            self.pixels[row * 2 * self.columns + column],
+                            self.pixels[(row * 2 + 1) * self.columns + column],
+                        ),
+                    };
+
+                if colors != Some((foreground, background)) {
+                    queue!(
+                        out,
+                        SetForegroundColor(foreground),
+                        SetBackgroundColor(background)
+                    )?;
+                    colors = Some((foreground, background));
+                }
+                queue!(out, Print(symbol))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Switches the terminal to a screen of its own without the cursor and line buffering, and back when dropped
+// (also when the game panics).
+struct Screen {
+    out: Stdout,
+}
+impl Screen {
+    fn open() -> io::Result<Self> {
+        enable_raw_mode()?;
+        let mut out = io::stdout();
+        execute!(out, EnterAlternateScreen, Hide)?;
+
+        Ok(Self { out })
+    }
+}
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, ResetColor, Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}
+
+// A single player game with the same flow as in the window: the autopilot flies on the start screen,
+// a flap starts the game and after a crash the next flap starts a new one.
+struct Game {
+    sim: Simulation,
+    autopilot: Autopilot,
+    locale: Locale,
+    // The course of every game when started with --seed.
+    seed: Option<u64>,
+    best_score: i128,
+    events: Vec<GameEvent>,
+}
+impl Game {
+    fn new(locale: Locale, seed: Option<u64>) -> Self {
+        Self {
+            sim: Simulation::new(GameMode::Single),
+            autopilot: Autopilot::new(),
+            locale,
+            seed,
+            best_score: 0,
+            events: Vec::new(),
+        }
+    }
+
+    fn tick(&mut self, flap: bool) {
+        // Restarts the game if player is dead.
+        if self.sim.play_state == PlayState::Dead {
+            self.best_score = self.best_score.max(self.sim.teams[0].score);
+            self.sim.restart();
+        }
+
+        let playing = self.sim.play_state.is_playing();
+        let attract = self.sim.play_state == PlayState::StartScreen;
+        let flaps = [if attract {
+            flap || self
+                .autopilot
+                .decide(&self.sim.players[0].entity, &self.sim)
+        } else {
+            flap
+        }];
+
+        let result = self.sim.step(&flaps, &mut self.events);
+        // There are no sounds or effects in the terminal.
+        self.events.clear();
+
+        // The pipes and enemies of the attract mode are cleared away for the real game.
+        if !playing && flap && self.sim.players[0].entity.has_jumped {
+            self.sim.clear_course();
+            self.sim.play_state = PlayState::Play;
+
+            if let Some(seed) = self.seed {
+                self.sim.seed(seed);
+            }
+        }
+
+        if result.over {
+            self.best_score = self.best_score.max(self.sim.teams[0].score);
+        }
+    }
+
+    fn draw(&self, columns: u16, rows: u16) -> Canvas {
+        let mut canvas = Canvas::new(columns as usize, rows as usize);
+        if columns < MIN_COLUMNS || rows < MIN_ROWS {
+            let text = self.locale.format(
+                "tui.too_small",
+                &[
+                    ("width", &MIN_COLUMNS.to_string()),
+                    ("height", &MIN_ROWS.to_string()),
+                ],
+            );
+            canvas.write(0, 0, &text);
+            return canvas;
+        }
+
+        let sim = &self.sim;
+        for pipe in sim.pipes.iter() {
+            canvas.fill(&pipe.top_zone, PIPE);
+            canvas.fill(&pipe.bottom_zone, PIPE);
+        }
+        // The ground hides the bottom of the pipes, like in the window.
+        canvas.fill(
+            &Rect::new(0.0, FLOOR_LEVEL, SCREEN_WIDTH, SCREEN_HEIGHT - FLOOR_LEVEL),
+            GROUND,
+        );
+        for enemy in sim.enemies.iter() {
+            canvas.fill(&enemy.zone, ENEMY);
+        }
+        for boost in sim.boosts.iter() {
+            let (r, g, b) = boost.effect.color().to_rgb();
+            canvas.fill(&boost.zone, Color::Rgb { r, g, b });
+        }
+        for player in sim.players.iter() {
+            let color = if player.alive { FERRIS } else { FERRIS_DOWN };
+            canvas.fill(&player.entity.zone, color);
+        }
+
+        // The score, lifes and speed on the top row and the keys on the bottom one.
+        let team = &sim.teams[0];
+        let status = format!(
+            " {}   {}   {} ",
+            self.locale
+                .format("tui.score", &[("score", &team.score.to_string())]),
+            self.locale
+                .format("tui.lifes", &[("lifes", &team.lifes.to_string())]),
+            self.locale
+                .format("speed", &[("speed", &format!("{:.1}", sim.multiplier))])
+        );
+        canvas.write(0, 0, &status);
+        canvas.write_right(
+            0,
+            &format!(
+                " {} ",
+                self.locale
+                    .format("best_score", &[("score", &self.best_score.to_string())])
+            ),
+        );
+        canvas.write_centered(
+            rows as usize - 1,
+            &format!(" {} ", self.locale.get("tui.quit_hint")),
+        );
+
+        let middle = rows as usize / 2;
+        match sim.play_state {
+            PlayState::StartScreen => {
+                canvas.write_centered(middle - 2, &format!(" {} ", self.locale.get("title")));
+                canvas.write_centered(middle, &format!(" {} ", self.locale.get("start_hint")));
+            }
+            PlayState::Dead => {
+                canvas.write_centered(middle - 2, &format!(" {} ", self.locale.get("game_over")));
+                canvas.write_centered(middle, &format!(" {} ", self.locale.get("restart_hint")));
+            }
+            PlayState::Play => (),
+        }
+
+        canvas
+    }
+}
+
+fn play(game: &mut Game, screen: &mut Screen) -> io::Result<()> {
+    let tick_length = Duration::from_nanos(TICK_NANOS as u64);
+    let mut next_tick = Instant::now();
+    let mut ticks: u64 = 0;
+    let mut size = (0, 0);
+
+    loop {
+        // Waits for the next tick, reading the keys pressed in the meantime.
+        let mut flap = false;
+        while event::poll(next_tick.saturating_duration_since(Instant::now()))? {
+            let key = match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => key,
+                _ => continue,
+            };
+
+            match key.code {
+                KeyCode::Char(' ') | KeyCode::Up | KeyCode::Char('w') => flap = true,
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(())
+                }
+                _ => (),
+            }
+        }
+
+        next_tick += tick_length;
+        if Instant::now() > next_tick + tick_length * MAX_TICKS_BEHIND {
+            next_tick = Instant::now();
+        }
+
+        game.tick(flap);
+        ticks += 1;
+        if ticks % TICKS_PER_FRAME != 0 {
+            continue;
+        }
+
+        // The whole frame is written at once, so it does not flicker.
+        let (columns, rows) = terminal::size()?;
+        let mut frame = Vec::new();
+        if (columns, rows) != size {
+            queue!(frame, ResetColor, Clear(ClearType::All))?;
+            size = (columns, rows);
+        }
+        game.draw(columns, rows).render(&mut frame)?;
+
+        screen.out.write_all(&frame)?;
+        screen.out.flush()?;
+    }
+}
+
+fn main() {
+    // A course that stays the same in every game: --seed <number>, a random course when it is not a number.
+    let seed = arg_value("--seed").and_then(|seed| seed.parse().ok());
+    // The language of the texts: --lang <language>, one of the string tables in resources/lang.
+    let language = arg_value("--lang").unwrap_or_else(|| DEFAULT_LANGUAGE.to_string());
+    let locale = Locale::from_folder(Path::new("./resources"), &language).unwrap();
+
+    let mut game = Game::new(locale, seed);
+    let mut screen = Screen::open().unwrap();
+    let result = play(&mut game, &mut screen);
+
+    // The terminal is set back before the error is shown.
+    drop(screen);
+    result.unwrap();
+}
+
+// The value after an option, e.g. "42" for --seed 42.
+fn arg_value(option: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == option)?;
+
+    args.get(index + 1).cloned()
+}
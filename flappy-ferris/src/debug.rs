@@ -0,0 +1,146 @@
+use ggez::graphics::{self, Color, Rect};
+use ggez::mint::{Point2, Vector2};
+use ggez::{Context, GameResult};
+
+// The time scales the game can run at, switched with F7 (slower) and F8 (faster).
+pub const TIME_SCALES: [f32; 6] = [0.1, 0.25, 0.5, 1.0, 2.0, 4.0];
+pub const NORMAL_TIME_SCALE: usize = 3;
+// Velocity vectors are drawn this many times longer than the movement in a single frame.
+pub const VECTOR_SCALE: f32 = 6.0;
+
+// Used for debugging overlapping of different entities.
+// Source: rust-shooter game in GitHub by andrew.
+pub fn draw_outline(bounding_box: graphics::Rect, ctx: &mut Context) -> GameResult<()> {
+    let draw_mode =
+        graphics::DrawMode::Stroke(graphics::StrokeOptions::default().with_line_width(1.0));
+    let red = graphics::Color::from_rgb(255, 0, 0);
+    let outline = graphics::MeshBuilder::new()
+        .rectangle(draw_mode, bounding_box, red)?
+        .build(ctx)?;
+
+    graphics::draw(ctx, &outline, graphics::DrawParam::default())?;
+
+    Ok(())
+}
+
+// Draws an arrow-less line showing where an entity is heading (velocity is in pixels per frame).
+pub fn draw_vector(ctx: &mut Context, from: Point2<f32>, velocity: Vector2<f32>) -> GameResult {
+    let to = Point2 {
+        x: from.x + velocity.x * VECTOR_SCALE,
+        y: from.y + velocity.y * VECTOR_SCALE,
+    };
+
+    // A line with no length can not be built.
+    if (to.x - from.x).abs() < 1.0 && (to.y - from.y).abs() < 1.0 {
+        return Ok(());
+    }
+
+    let line = graphics::Mesh::new_line(ctx, &[from, to], 2.0, Color::from_rgb(255, 0, 255))?;
+    graphics::draw(ctx, &line, graphics::DrawParam::default())
+}
+
+// The debug overlay (toggled with F3). While it is shown the game can also be paused (F5),
+// advanced a single frame at a time (F6) and slowed down or sped up (F7 and F8).
+pub struct DebugOverlay {
+    pub enabled: bool,
+    pub paused: bool,
+    pub time_scale: usize,
+
+    step_requested: bool,
+    // Fractions of updates left over from the previous ticks when running at a different time scale.
+    pending_updates: f32,
+}
+impl DebugOverlay {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            paused: false,
+            time_scale: NORMAL_TIME_SCALE,
+
+            step_requested: false,
+            pending_updates: 0.0,
+        }
+    }
+
+    // Hiding the overlay also resumes the game at its normal speed.
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+
+        if !self.enabled {
+            self.paused = false;
+            self.time_scale = NORMAL_TIME_SCALE;
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // Runs a single update on the next tick (only while paused).
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.step_requested = true;
+    }
+
+    pub fn slower(&mut self) {
+        self.time_scale = self.time_scale.saturating_sub(1);
+    }
+
+    pub fn faster(&mut self) {
+        self.time_scale = (self.time_scale + 1).min(TIME_SCALES.len() - 1);
+    }
+
+    pub fn scale(&self) -> f32 {
+        TIME_SCALES[self.time_scale]
+    }
+
+    // How many times the game should be updated during this tick.
+    // The game moves by a fixed amount every update, so changing the time scale changes how many updates there are.
+    pub fn updates_this_tick(&mut self) -> usize {
+        if self.paused {
+            let step = self.step_requested;
+            self.step_requested = false;
+
+            return step as usize;
+        }
+
+        self.pending_updates += self.scale();
+        let updates = self.pending_updates.floor();
+        self.pending_updates -= updates;
+
+        updates as usize
+    }
+
+    // Draws the given lines of information in the top left corner.
+    pub fn draw_info(
+        &self,
+        ctx: &mut Context,
+        font: graphics::Font,
+        lines: &[String],
+    ) -> GameResult {
+        let mut text = graphics::Text::new(lines.join("\n"));
+        text.set_font(font, graphics::PxScale::from(20.0));
+
+        let position = Point2 { x: 10.0, y: 10.0 };
+        let background = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            Rect::new(
+                position.x - 5.0,
+                position.y - 5.0,
+                text.width(ctx) + 10.0,
+                text.height(ctx) + 10.0,
+            ),
+            Color::from_rgba(0, 0, 0, 160),
+        )?;
+
+        graphics::draw(ctx, &background, graphics::DrawParam::default())?;
+        graphics::draw(
+            ctx,
+            &text,
+            graphics::DrawParam::default()
+                .dest(position)
+                .color(Color::WHITE),
+        )
+    }
+}
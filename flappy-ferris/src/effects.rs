@@ -0,0 +1,133 @@
+use crate::entities::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use ggez::graphics::{self, Color, Rect};
+use ggez::mint::Vector2;
+use ggez::{Context, GameResult};
+use rand::Rng;
+
+// Used for the strength (in pixels) and length (in seconds) of the effects.
+pub const HIT_SHAKE: (f32, f32) = (8.0, 0.3);
+pub const DEATH_SHAKE: (f32, f32) = (14.0, 0.5);
+pub const HIT_STOP_TIME: f32 = 0.12;
+pub const FLASH_TIME: f32 = 0.4;
+pub const TINT_ALPHA: f32 = 0.15;
+
+// Every effect can be turned off from the settings menu (e.g. for players sensitive to motion or flashing lights).
+pub struct EffectSettings {
+    pub screen_shake: bool,
+    pub hit_stop: bool,
+    pub flash: bool,
+    pub tint: bool,
+}
+impl EffectSettings {
+    pub fn new() -> Self {
+        Self {
+            screen_shake: true,
+            hit_stop: true,
+            flash: true,
+            tint: true,
+        }
+    }
+}
+
+// The camera and screen effects: shaking, freezing the game for a moment, flashing and tinting the screen.
+pub struct Effects {
+    pub settings: EffectSettings,
+    pub tint: Option<Color>,
+
+    shake_strength: f32,
+    shake_time: f32,
+    shake_duration: f32,
+    hit_stop_time: f32,
+    flash_time: f32,
+}
+impl Effects {
+    pub fn new() -> Self {
+        Self {
+            settings: EffectSettings::new(),
+            tint: None,
+
+            shake_strength: 0.0,
+            shake_time: 0.0,
+            shake_duration: 0.0,
+            hit_stop_time: 0.0,
+            flash_time: 0.0,
+        }
+    }
+
+    // Shakes the camera with the given (strength, duration). A weaker shake does not cut a stronger one short.
+    pub fn shake(&mut self, (strength, duration): (f32, f32)) {
+        if self.settings.screen_shake && strength >= self.shake_strength * self.shake_fade() {
+            self.shake_strength = strength;
+            self.shake_time = duration;
+            self.shake_duration = duration;
+        }
+    }
+
+    pub fn hit_stop(&mut self, duration: f32) {
+        if self.settings.hit_stop {
+            self.hit_stop_time = duration;
+        }
+    }
+
+    pub fn flash(&mut self, duration: f32) {
+        if self.settings.flash {
+            self.flash_time = duration;
+        }
+    }
+
+    // While true the game should not be updated.
+    pub fn is_frozen(&self) -> bool {
+        self.hit_stop_time > 0.0
+    }
+
+    // Counts down the effects (delta is in seconds).
+    pub fn update(&mut self, delta: f32) {
+        self.shake_time = (self.shake_time - delta).max(0.0);
+        self.hit_stop_time = (self.hit_stop_time - delta).max(0.0);
+        self.flash_time = (self.flash_time - delta).max(0.0);
+    }
+
+    // How far the camera is moved by the shake this frame. The shake gets weaker until it stops.
+    pub fn camera_offset(&self) -> Vector2<f32> {
+        if self.shake_time <= 0.0 || !self.settings.screen_shake {
+            return Vector2 { x: 0.0, y: 0.0 };
+        }
+
+        let strength = self.shake_strength * self.shake_fade();
+        let mut rng = rand::thread_rng();
+
+        Vector2 {
+            x: rng.gen_range(-strength..=strength),
+            y: rng.gen_range(-strength..=strength),
+        }
+    }
+
+    // Draws the tint and the flash over the whole game area.
+    pub fn draw_overlay(&self, ctx: &mut Context) -> GameResult {
+        let screen = Rect::new(0.0, 0.0, SCREEN_WIDTH, SCREEN_HEIGHT);
+
+        if let (Some(mut tint), true) = (self.tint, self.settings.tint) {
+            tint.a = TINT_ALPHA;
+            let overlay =
+                graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), screen, tint)?;
+            graphics::draw(ctx, &overlay, graphics::DrawParam::default())?;
+        }
+
+        if self.flash_time > 0.0 && self.settings.flash {
+            let white = Color::new(1.0, 1.0, 1.0, self.flash_time / FLASH_TIME);
+            let overlay =
+                graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), screen, white)?;
+            graphics::draw(ctx, &overlay, graphics::DrawParam::default())?;
+        }
+
+        Ok(())
+    }
+
+    fn shake_fade(&self) -> f32 {
+        if self.shake_duration > 0.0 {
+            self.shake_time / self.shake_duration
+        } else {
+            0.0
+        }
+    }
+}
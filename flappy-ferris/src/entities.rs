@@ -1,12 +1,22 @@
+use crate::animation::Animator;
 use crate::assets::Assets;
-use ggez::event::KeyCode;
 use ggez::graphics::Rect;
-use ggez::input::keyboard;
 use ggez::mint::{Point2, Vector2};
 use ggez::{graphics, Context, GameResult};
 
-// Used for toggling outline drawing for entities.
-pub const DEBUG_MODE: bool = false;
+// Used for choosing the player hitbox.
+// When false (the default) the hitbox keeps the size of the upright sprite no matter how Ferris is tilted,
+// so collisions feel the same as in the original game and do not depend on the animation.
+// When true the hitbox is the bounding box of the rotated sprite, which is bigger (and harder) while nose-diving.
+pub const ROTATE_HITBOX: bool = false;
+
+// Used for tilting and stretching the player sprite (angles are in radians).
+pub const TILT_PER_VELOCITY: f32 = 0.09;
+pub const MAX_TILT_UP: f32 = -0.45;
+pub const MAX_TILT_DOWN: f32 = 1.4;
+pub const TILT_SMOOTHING: f32 = 0.2;
+pub const STRETCH_AMOUNT: f32 = 0.15;
+pub const STRETCH_DECAY: f32 = 0.85;
 
 // Used for entity movement.
 pub const GRAVITY: f32 = 0.50;
@@ -29,23 +39,23 @@ pub const ENEMY_HEIGHT: f32 = 84.0;
 pub const BOOST_WIDTH: f32 = 64.0;
 pub const BOOST_HEIGHT: f32 = 64.0;
 
-// Used for debugging overlapping of different entities.
-// Source: rust-shooter game in GitHub by andrew.
-pub fn draw_outline(bounding_box: graphics::Rect, ctx: &mut Context) -> GameResult<()> {
-    if DEBUG_MODE {
-        let draw_mode =
-            graphics::DrawMode::Stroke(graphics::StrokeOptions::default().with_line_width(1.0));
-        let red = graphics::Color::from_rgb(255, 0, 0);
-        let outline = graphics::MeshBuilder::new()
-            .rectangle(draw_mode, bounding_box, red)
-            .unwrap()
-            .build(ctx)
-            .unwrap();
-
-        graphics::draw(ctx, &outline, graphics::DrawParam::default())?;
-    }
-
-    Ok(())
+// Draws the current frame of an animation (given by its id in the asset manifest).
+pub fn draw_animated(
+    ctx: &mut Context,
+    assets: &Assets,
+    animation: &str,
+    animator: &Animator,
+    param: graphics::DrawParam,
+) -> GameResult {
+    let set = assets.animation(animation);
+    let sheet = assets.image(&set.sheet);
+    let src = set.frame_rect(
+        animator.frame(set),
+        sheet.width() as f32,
+        sheet.height() as f32,
+    );
+
+    graphics::draw(ctx, sheet, param.src(src))
 }
 
 // States the game could be in.
@@ -66,10 +76,34 @@ impl PlayState {
 }
 
 // Different types of boosts.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum BoostType {
     SpeedUp,
     SlowDown,
     BonusLife,
+    // Only in co-op, brings a downed partner back.
+    Revive,
+}
+impl BoostType {
+    // The clip of the boosts animation for this type.
+    pub fn clip(&self) -> &'static str {
+        match self {
+            BoostType::SpeedUp => "speed_up",
+            BoostType::SlowDown => "slow_down",
+            BoostType::BonusLife => "life",
+            BoostType::Revive => "revive",
+        }
+    }
+
+    // The main color of the boost sprite.
+    pub fn color(&self) -> graphics::Color {
+        match self {
+            BoostType::SpeedUp => graphics::Color::from_rgb(255, 196, 0),
+            BoostType::SlowDown => graphics::Color::from_rgb(66, 135, 245),
+            BoostType::BonusLife => graphics::Color::from_rgb(230, 40, 60),
+            BoostType::Revive => graphics::Color::from_rgb(90, 230, 110),
+        }
+    }
 }
 
 // Used for moving the player. Only y-based movement needed since they move only up and down.
@@ -92,38 +126,56 @@ pub struct PlayerEntity {
     pub physics: Physics,
     pub zone: Rect,
     pub can_jump: bool,
+    // True only during the frame in which the player flapped.
+    pub has_jumped: bool,
+    pub animator: Animator,
+    // The current tilt of the sprite and how much it is still stretched after the last jump (from 1.0 down to 0.0).
+    pub rotation: f32,
+    pub stretch: f32,
+    // Multiplied with the colors of the sprite, so players can be told apart.
+    pub tint: graphics::Color,
 }
 impl PlayerEntity {
     pub fn new() -> Self {
+        PlayerEntity::at(SCREEN_WIDTH / 4.0)
+    }
+
+    // A player at the given horizontal position (used when there is more than one player).
+    pub fn at(x: f32) -> Self {
         Self {
-            position: Point2 {
-                x: SCREEN_WIDTH / 4.0,
-                y: MIDDLE,
-            },
+            position: Point2 { x, y: MIDDLE },
             physics: Physics::new(),
             zone: Rect {
-                x: (SCREEN_WIDTH / 4.0) - (FERRIS_WIDTH / 2.0),
+                x: x - (FERRIS_WIDTH / 2.0),
                 y: MIDDLE - (FERRIS_HEIGHT / 2.0),
                 w: FERRIS_WIDTH,
                 h: FERRIS_HEIGHT,
             },
             can_jump: true,
+            has_jumped: false,
+            animator: Animator::new("glide"),
+            rotation: 0.0,
+            stretch: 0.0,
+            tint: graphics::Color::WHITE,
         }
     }
 
-    pub fn update(&mut self, ctx: &mut Context, state: &PlayState) -> PlayState {
+    // The jump is edge-triggered: holding the flap input down (key, click or touch) jumps only once.
+    pub fn update(&mut self, flap_held: bool, state: &PlayState) -> PlayState {
         let physics = &mut self.physics;
         physics.acceleration = GRAVITY;
+        self.has_jumped = false;
 
-        if !(keyboard::pressed_keys(ctx).contains(&KeyCode::Space)) && !(self.can_jump) {
+        if !flap_held && !(self.can_jump) {
             self.can_jump = true;
         }
 
         let mut new_state = state.clone();
-        if keyboard::is_key_pressed(ctx, KeyCode::Space) && self.can_jump {
+        if flap_held && self.can_jump {
             let physics = &mut self.physics;
 
             self.can_jump = false;
+            self.has_jumped = true;
 
             PlayerEntity::jump(physics);
 
@@ -132,36 +184,67 @@ impl PlayerEntity {
             }
         }
 
-        if new_state == PlayState::StartScreen {
-            self.auto_jump();
-        }
-
         self.change_player_position();
         self.prevent_going_out();
+        self.tilt();
+
+        if ROTATE_HITBOX {
+            self.fit_zone_to_rotation();
+        }
 
         new_state
     }
 
-    pub fn draw(&mut self, ctx: &mut Context, assets: &Assets) -> GameResult {
-        let p = &self.physics;
+    // Picks the clip that fits what the player is doing. The one-shot clips (flap and hurt) are played until their end.
+    pub fn animate(&mut self, assets: &Assets, delta: f32) {
+        let set = assets.animation("ferris");
+        let animator = &mut self.animator;
+        animator.update(delta);
 
-        let x = if p.velocity >= 0.0 {
-            &assets.ferris_stable_image
-        } else {
-            &assets.ferris_jumping_image
-        };
+        if animator.is_playing("death")
+            || (animator.is_playing("hurt") && !animator.is_finished(set))
+        {
+            return;
+        }
 
-        graphics::draw(
+        if self.has_jumped {
+            animator.restart("flap");
+        } else if !animator.is_playing("flap") || animator.is_finished(set) {
+            let clip = if self.physics.velocity < 0.0 {
+                "glide"
+            } else {
+                "fall"
+            };
+            animator.play(clip);
+        }
+    }
+
+    pub fn hurt(&mut self) {
+        self.animator.restart("hurt");
+    }
+
+    pub fn die(&mut self) {
+        self.animator.play("death");
+    }
+
+    pub fn draw(&mut self, ctx: &mut Context, assets: &Assets) -> GameResult {
+        draw_animated(
             ctx,
-            x,
+            assets,
+            "ferris",
+            &self.animator,
             graphics::DrawParam::default()
                 .dest(self.position.clone())
-                .offset(Point2 { x: 0.5, y: 0.5 }),
+                .offset(Point2 { x: 0.5, y: 0.5 })
+                .rotation(self.rotation)
+                .scale(Vector2 {
+                    x: 1.0 - STRETCH_AMOUNT * self.stretch,
+                    y: 1.0 + STRETCH_AMOUNT * self.stretch,
+                })
+                .color(self.tint),
         )
         .unwrap();
 
-        draw_outline(self.zone, ctx).unwrap();
-
         Ok(())
     }
 
@@ -171,15 +254,6 @@ impl PlayerEntity {
         physics.velocity = -JUMP;
     }
 
-    // Used during the StartScreen state.
-    fn auto_jump(&mut self) {
-        let physics = &mut self.physics;
-
-        if self.position.y >= MIDDLE {
-            PlayerEntity::jump(physics);
-        }
-    }
-
     // Calculates the changes to position and zone of player after jumping.
     fn change_player_position(&mut self) {
         let physics = &mut self.physics;
@@ -199,6 +273,33 @@ impl PlayerEntity {
         self.zone.translate(offset);
     }
 
+    // Tilts Ferris up after a flap and down while falling (smoothly, following the velocity).
+    // A jump also stretches the sprite for a moment.
+    fn tilt(&mut self) {
+        let target = (self.physics.velocity * TILT_PER_VELOCITY).clamp(MAX_TILT_UP, MAX_TILT_DOWN);
+        self.rotation += (target - self.rotation) * TILT_SMOOTHING;
+
+        self.stretch = if self.has_jumped {
+            1.0
+        } else {
+            self.stretch * STRETCH_DECAY
+        };
+    }
+
+    // Makes the zone the bounding box of the rotated sprite (only used if ROTATE_HITBOX is on).
+    fn fit_zone_to_rotation(&mut self) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let w = FERRIS_WIDTH * cos.abs() + FERRIS_HEIGHT * sin.abs();
+        let h = FERRIS_WIDTH * sin.abs() + FERRIS_HEIGHT * cos.abs();
+
+        self.zone = Rect {
+            x: self.position.x - (w / 2.0),
+            y: self.position.y - (h / 2.0),
+            w,
+            h,
+        };
+    }
+
     // Stops the player from going over the top and "cheating" the pipes.
     fn prevent_going_out(&mut self) {
         self.position.y = if self.position.y < (FERRIS_HEIGHT / 2.0) {
@@ -211,8 +312,21 @@ impl PlayerEntity {
 
     // Sends the player back to the middle of the screen.
     pub fn prevent_hitting_ground(&mut self) {
-        self.position.y = MIDDLE;
-        self.zone.y = MIDDLE - (FERRIS_HEIGHT / 2.0);
+        self.place_at(MIDDLE);
+    }
+
+    // Moves the player to the given height.
+    pub fn place_at(&mut self, y: f32) {
+        self.position.y = y;
+        self.zone.y = y - (FERRIS_HEIGHT / 2.0);
+    }
+
+    // Lets a player who crashed lie on the ground instead of falling through it.
+    pub fn land(&mut self) {
+        if self.hits_ground() {
+            self.place_at(FLOOR_LEVEL);
+            self.physics.velocity = 0.0;
+        }
     }
 
     // Checks if the player is touching the given ground level.
@@ -227,6 +341,8 @@ pub struct PipeEntity {
     pub top_zone: Rect,
     pub bottom_zone: Rect,
     pub is_passed: bool,
+    // The players who lost a life on it, so they are not hit again while flying through it (and get no point for it).
+    pub hit_by: Vec<usize>,
 }
 impl PipeEntity {
     pub fn new(y: f32) -> Self {
@@ -248,6 +364,7 @@ impl PipeEntity {
                 h: SCREEN_HEIGHT - y - PIPE_GAP,
             },
             is_passed: false,
+            hit_by: Vec::new(),
         }
     }
 
@@ -269,14 +386,14 @@ impl PipeEntity {
     }
 
     pub fn draw(&mut self, ctx: &mut Context, assets: &Assets) -> GameResult {
-        let top = &assets.pipe_top_image;
+        let top = assets.image("pipe_top");
         let dest_top = Point2 {
             x: self.position.x,
             y: self.position.y,
         };
         let offset_top = Point2 { x: 0.5, y: 1.0 };
 
-        let bottom = &assets.pipe_bottom_image;
+        let bottom = assets.image("pipe_bottom");
         let dest_bottom = Point2 {
             x: self.position.x,
             y: self.position.y + PIPE_GAP,
@@ -301,9 +418,6 @@ impl PipeEntity {
         )
         .unwrap();
 
-        draw_outline(self.bottom_zone, ctx).unwrap();
-        draw_outline(self.top_zone, ctx).unwrap();
-
         Ok(())
     }
 }
@@ -313,6 +427,11 @@ pub struct EnemyEntity {
     pub position: Point2<f32>,
     pub zone: Rect,
     pub is_passed: bool,
+    // The players who lost a life on it, so they are not hit again while it flies through them.
+    pub hit_by: Vec<usize>,
+    // Set once the enemy flies past the player (used for playing its sound only once).
+    pub is_behind: bool,
+    pub animator: Animator,
 }
 impl EnemyEntity {
     pub fn new(y: f32) -> Self {
@@ -328,6 +447,9 @@ impl EnemyEntity {
                 h: ENEMY_HEIGHT,
             },
             is_passed: false,
+            hit_by: Vec::new(),
+            is_behind: false,
+            animator: Animator::new("fly"),
         }
     }
 
@@ -346,21 +468,24 @@ impl EnemyEntity {
         self.zone.translate(offset);
     }
 
+    pub fn animate(&mut self, delta: f32) {
+        self.animator.update(delta);
+    }
+
     pub fn draw(&mut self, ctx: &mut Context, assets: &Assets) -> GameResult {
-        let x = &assets.enemy_image;
         let offset = Point2 { x: 0.5, y: 0.5 };
 
-        graphics::draw(
+        draw_animated(
             ctx,
-            x,
+            assets,
+            "enemy",
+            &self.animator,
             graphics::DrawParam::default()
                 .dest(self.position.clone())
                 .offset(offset),
         )
         .unwrap();
 
-        draw_outline(self.zone, ctx).unwrap();
-
         Ok(())
     }
 }
@@ -372,6 +497,7 @@ pub struct BoostEntity {
     pub effect: BoostType,
     pub is_passed: bool,
     pub is_collected: bool,
+    pub animator: Animator,
 }
 impl BoostEntity {
     pub fn new(y: f32, val: f32) -> Self {
@@ -384,6 +510,13 @@ impl BoostEntity {
             eff = BoostType::BonusLife;
         }
 
+        BoostEntity::with_effect(y, eff)
+    }
+
+    // A boost of the given type (used for the ones that are not picked at random).
+    pub fn with_effect(y: f32, eff: BoostType) -> Self {
+        let animator = Animator::new(eff.clip());
+
         Self {
             position: Point2 {
                 x: SCREEN_WIDTH + (BOOST_WIDTH / 2.0),
@@ -398,6 +531,7 @@ impl BoostEntity {
             effect: eff,
             is_passed: false,
             is_collected: false,
+            animator,
         }
     }
 
@@ -416,26 +550,24 @@ impl BoostEntity {
         self.zone.translate(offset);
     }
 
-    pub fn draw(&mut self, ctx: &mut Context, assets: &Assets) -> GameResult {
-        let x = match self.effect {
-            BoostType::BonusLife => &assets.boost_life_image,
-            BoostType::SlowDown => &assets.boost_slow_down_image,
-            BoostType::SpeedUp => &assets.boost_speed_up_image,
-        };
+    pub fn animate(&mut self, delta: f32) {
+        self.animator.update(delta);
+    }
 
+    pub fn draw(&mut self, ctx: &mut Context, assets: &Assets) -> GameResult {
         let offset = Point2 { x: 0.5, y: 0.5 };
 
-        graphics::draw(
+        draw_animated(
             ctx,
-            x,
+            assets,
+            "boosts",
+            &self.animator,
             graphics::DrawParam::default()
                 .dest(self.position.clone())
                 .offset(offset),
         )
         .unwrap();
 
-        draw_outline(self.zone, ctx).unwrap();
-
         Ok(())
     }
 }
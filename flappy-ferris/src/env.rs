@@ -0,0 +1,394 @@
+use crate::entities::{PlayState, FLOOR_LEVEL, JUMP, SCREEN_WIDTH};
+use crate::events::GameEvent;
+use crate::players::GameMode;
+use crate::sim::Simulation;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+// Used for the observation: how many of the next pipes and enemies it describes.
+pub const OBSERVED_PIPES: usize = 2;
+pub const OBSERVED_ENEMIES: usize = 2;
+pub const OBSERVATION_SIZE: usize = 2 + OBSERVED_PIPES * 3 + OBSERVED_ENEMIES * 2;
+
+// What an agent sees after each step, always OBSERVATION_SIZE numbers, mostly between -1 and 1:
+//   - the height of Ferris (0 at the top, 1 at the ground) and its velocity (-1 right after a flap),
+//   - for each of the next OBSERVED_PIPES pipes: how far ahead it is and how far above and below Ferris its gap ends,
+//   - for each of the next OBSERVED_ENEMIES enemies: how far ahead and how far below Ferris it is.
+// The distances are in screen widths and heights. Pipes and enemies that are not there yet are far ahead,
+// pipes with their gap as big as the whole screen and enemies at the height of Ferris.
+pub type Observation = Vec<f32>;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Action {
+    Wait,
+    // Works like holding the flap key down during the step: a flap needs a Wait before the next one.
+    Flap,
+}
+
+// How much each thing that can happen is worth to the agent.
+#[derive(Debug, Clone, Copy)]
+pub struct Rewards {
+    // For every tick Ferris is still flying.
+    pub survival: f32,
+    pub pipe: f32,
+    pub boost: f32,
+    pub life_lost: f32,
+    // For the crash that ends the game.
+    pub death: f32,
+}
+impl Rewards {
+    pub fn new() -> Self {
+        Self {
+            survival: 0.01,
+            pipe: 1.0,
+            boost: 0.5,
+            life_lost: -1.0,
+            death: -1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EnvConfig {
+    pub rewards: Rewards,
+    // The action is repeated for this many ticks in every step.
+    pub ticks_per_step: usize,
+    // A game still going after this many ticks is cut short (None for no limit).
+    pub max_ticks: Option<u64>,
+}
+impl EnvConfig {
+    pub fn new() -> Self {
+        Self {
+            rewards: Rewards::new(),
+            ticks_per_step: 1,
+            max_ticks: None,
+        }
+    }
+}
+
+// More about the state of the game after a step, which the agent should not need to act.
+#[derive(Debug, Clone)]
+pub struct Info {
+    pub score: i128,
+    pub lifes: i128,
+    pub ticks: u64,
+    // Set if the game was cut short after max_ticks and not over because Ferris crashed.
+    pub truncated: bool,
+    pub events: Vec<GameEvent>,
+}
+
+// A single player game without a window, played one step at a time by an agent. Every game is on a seeded course,
+// so the same seed and actions always give the same game.
+pub struct Env {
+    pub config: EnvConfig,
+    pub sim: Simulation,
+    ticks: u64,
+    done: bool,
+}
+impl Env {
+    // The environment has to be reset before the first step.
+    pub fn new(config: EnvConfig) -> Self {
+        Self {
+            config,
+            sim: Simulation::new(GameMode::Single),
+            ticks: 0,
+            done: true,
+        }
+    }
+
+    // Starts a new game on the course of the seed.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.sim = Simulation::new(GameMode::Single);
+        self.sim.seed(seed);
+        self.sim.play_state = PlayState::Play;
+        self.ticks = 0;
+        self.done = false;
+
+        self.observe()
+    }
+
+    // Plays the action for one step. Stepping after the game is done changes nothing and gives no reward.
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool, Info) {
+        let rewards = self.config.rewards;
+        let mut reward = 0.0;
+        let mut events = Vec::new();
+
+        for _ in 0..self.config.ticks_per_step {
+            if self.done {
+                break;
+            }
+
+            let first_event = events.len();
+            let over = self.sim.step(&[action == Action::Flap], &mut events).over;
+            self.ticks += 1;
+
+            for event in events[first_event..].iter() {
+                reward += match event {
+                    GameEvent::PipePassed { .. } => rewards.pipe,
+                    GameEvent::BoostCollected => rewards.boost,
+                    GameEvent::LifeLost => rewards.life_lost,
+                    _ => 0.0,
+                };
+            }
+            if over {
+                reward += rewards.death;
+            } else {
+                reward += rewards.survival;
+            }
+
+            let out_of_time = self.config.max_ticks.map_or(false, |max| self.ticks >= max);
+            self.done = over || out_of_time;
+        }
+
+        let info = Info {
+            score: self.sim.teams[0].score,
+            lifes: self.sim.teams[0].lifes,
+            ticks: self.ticks,
+            truncated: self.done && self.sim.play_state.is_playing(),
+            events,
+        };
+
+        (self.observe(), reward, self.done, info)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    pub fn observe(&self) -> Observation {
+        observe(&self.sim, 0)
+    }
+}
+
+// What the given player of the simulation sees (the population mode observes every player this way).
+pub fn observe(sim: &Simulation, player: usize) -> Observation {
+    let entity = &sim.players[player].entity;
+    let (x, y) = (entity.position.x, entity.position.y);
+    let left = entity.zone.x;
+
+    let mut observation = Vec::with_capacity(OBSERVATION_SIZE);
+    observation.push(y / FLOOR_LEVEL);
+    observation.push(entity.physics.velocity / JUMP);
+
+    // The pipes and enemies all move at the same speed, so they are already in the order they come in.
+    let mut pipes = sim
+        .pipes
+        .iter()
+        .filter(|pipe| pipe.top_zone.x + pipe.top_zone.w > left);
+    for _ in 0..OBSERVED_PIPES {
+        let (ahead, gap_top, gap_bottom) = match pipes.next() {
+            Some(pipe) => (
+                pipe.position.x - x,
+                pipe.top_zone.y + pipe.top_zone.h,
+                pipe.bottom_zone.y,
+            ),
+            None => (SCREEN_WIDTH, 0.0, FLOOR_LEVEL),
+        };
+        observation.push(ahead / SCREEN_WIDTH);
+        observation.push((gap_top - y) / FLOOR_LEVEL);
+        observation.push((gap_bottom - y) / FLOOR_LEVEL);
+    }
+
+    let mut enemies = sim
+        .enemies
+        .iter()
+        .filter(|enemy| enemy.zone.x + enemy.zone.w > left);
+    for _ in 0..OBSERVED_ENEMIES {
+        let (ahead, below) = match enemies.next() {
+            Some(enemy) => (enemy.position.x - x, enemy.position.y - y),
+            None => (SCREEN_WIDTH, 0.0),
+        };
+        observation.push(ahead / SCREEN_WIDTH);
+        observation.push(below / FLOOR_LEVEL);
+    }
+
+    observation
+}
+
+enum Command {
+    Reset(u64),
+    Step(Action),
+}
+
+enum Outcome {
+    Reset(Observation),
+    Step((Observation, f32, bool, Info)),
+}
+
+// An environment in its own thread, waiting for commands.
+struct Worker {
+    commands: Sender<Command>,
+    outcomes: Receiver<Outcome>,
+    handle: JoinHandle<()>,
+}
+impl Worker {
+    fn spawn(config: EnvConfig) -> Self {
+        let (commands, command_receiver) = channel();
+        let (outcome_sender, outcomes) = channel();
+
+        // The thread stops when the commands channel is closed (when the ParallelEnv is dropped).
+        let handle = thread::spawn(move || {
+            let mut env = Env::new(config);
+            for command in command_receiver {
+                let outcome = match command {
+                    Command::Reset(seed) => Outcome::Reset(env.reset(seed)),
+                    Command::Step(action) => Outcome::Step(env.step(action)),
+                };
+                if outcome_sender.send(outcome).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            commands,
+            outcomes,
+            handle,
+        }
+    }
+}
+
+// Many environments stepped at the same time, each one in its own thread. The seeds, actions and results
+// are in the same order as the environments.
+pub struct ParallelEnv {
+    workers: Vec<Worker>,
+}
+impl ParallelEnv {
+    pub fn new(count: usize, config: EnvConfig) -> Self {
+        Self {
+            workers: (0..count).map(|_| Worker::spawn(config)).collect(),
+        }
+    }
+
+    pub fn reset(&mut self, seeds: &[u64]) -> Vec<Observation> {
+        assert_eq!(seeds.len(), self.workers.len(), "one seed per environment");
+        self.send(seeds.iter().map(|&seed| Command::Reset(seed)))
+            .map(|outcome| match outcome {
+                Outcome::Reset(observation) => observation,
+                Outcome::Step(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    pub fn step(&mut self, actions: &[Action]) -> Vec<(Observation, f32, bool, Info)> {
+        assert_eq!(
+            actions.len(),
+            self.workers.len(),
+            "one action per environment"
+        );
+        self.send(actions.iter().map(|&action| Command::Step(action)))
+            .map(|outcome| match outcome {
+                Outcome::Step(result) => result,
+                Outcome::Reset(_) => unreachable!(),
+            })
+            .collect()
+    }
+
+    // Sends every worker its command first, so they all work at the same time, and then waits for them.
+    fn send(
+        &mut self,
+        commands: impl Iterator<Item = Command>,
+    ) -> impl Iterator<Item = Outcome> + '_ {
+        for (worker, command) in self.workers.iter().zip(commands) {
+            worker.commands.send(command).unwrap();
+        }
+
+        self.workers
+            .iter()
+            .map(|worker| worker.outcomes.recv().unwrap())
+    }
+}
+impl Drop for ParallelEnv {
+    fn drop(&mut self) {
+        // A worker that panicked already printed why, so it is only mentioned here instead of panicking again.
+        for worker in self.workers.drain(..) {
+            drop(worker.commands);
+            if worker.handle.join().is_err() {
+                eprintln!("An environment thread stopped with a panic");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::autopilot::Autopilot;
+
+    type Outcome = (Observation, f32, bool, i128);
+
+    const STEPS: usize = 1500;
+
+    // The autopilot flies the game, so it lasts long enough to go through pipes, enemies and boosts.
+    // Returns the actions it took (waiting after the game is done).
+    fn fly(seed: u64) -> Vec<Action> {
+        let autopilot = Autopilot::new();
+        let mut env = Env::new(EnvConfig::new());
+        env.reset(seed);
+
+        (0..STEPS)
+            .map(|_| {
+                let flap = autopilot.decide(&env.sim.players[0].entity, &env.sim);
+                let action = if flap { Action::Flap } else { Action::Wait };
+                env.step(action);
+                action
+            })
+            .collect()
+    }
+
+    fn replay(env: &mut Env, seed: u64, actions: &[Action]) -> Vec<Outcome> {
+        let mut outcomes = vec![(env.reset(seed), 0.0, false, 0)];
+        for &action in actions {
+            let (observation, reward, done, info) = env.step(action);
+            outcomes.push((observation, reward, done, info.score));
+        }
+
+        outcomes
+    }
+
+    #[test]
+    fn same_seed_and_actions_give_the_same_game() {
+        let mut env = Env::new(EnvConfig::new());
+
+        for seed in [0, 42, u64::MAX] {
+            let actions = fly(seed);
+            let first = replay(&mut env, seed, &actions);
+            let second = replay(&mut env, seed, &actions);
+
+            assert_eq!(first, second);
+            assert!(
+                first.last().unwrap().3 > 0,
+                "the game ended too soon to tell"
+            );
+        }
+    }
+
+    #[test]
+    fn parallel_environments_match_single_ones() {
+        let seeds = [1, 2, 3, 4];
+        let actions: Vec<Vec<Action>> = seeds.iter().map(|&seed| fly(seed)).collect();
+
+        let expected: Vec<Vec<Outcome>> = seeds
+            .iter()
+            .zip(actions.iter())
+            .map(|(&seed, actions)| replay(&mut Env::new(EnvConfig::new()), seed, actions))
+            .collect();
+
+        let mut parallel = ParallelEnv::new(seeds.len(), EnvConfig::new());
+        let mut outcomes: Vec<Vec<Outcome>> = parallel
+            .reset(&seeds)
+            .into_iter()
+            .map(|observation| vec![(observation, 0.0, false, 0)])
+            .collect();
+        for step in 0..STEPS {
+            let step_actions: Vec<Action> = actions.iter().map(|actions| actions[step]).collect();
+            for (outcomes, (observation, reward, done, info)) in
+                outcomes.iter_mut().zip(parallel.step(&step_actions))
+            {
+                outcomes.push((observation, reward, done, info.score));
+            }
+        }
+
+        assert_eq!(outcomes, expected);
+    }
+}
@@ -0,0 +1,14 @@
+// Things that happen during the game. They are collected while updating and then handled in one place (e.g. for playing sounds).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GameEvent {
+    Flap,
+    // The player who got a point for passing a pipe.
+    PipePassed { player: usize },
+    LifeLost,
+    EnemyPassed,
+    BoostCollected,
+    BoostExpired,
+    Death,
+    MenuMove,
+    MenuSelect,
+}
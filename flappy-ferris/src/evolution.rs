@@ -0,0 +1,427 @@
+use crate::assets::Assets;
+use crate::entities::{PlayState, STRETCH_AMOUNT};
+use crate::env::observe;
+use crate::events::GameEvent;
+use crate::hud::{Label, TextStyle, HUD_MARGIN};
+use crate::locale::{Locale, DEFAULT_LANGUAGE};
+use crate::neural::Network;
+use crate::parallax::Parallax;
+use crate::players::GameMode;
+use crate::sim::{Simulation, TICKS_PER_SECOND, TICK_SECONDS};
+use crate::viewport::Viewport;
+use ggez::event::{self, EventHandler, KeyCode, KeyMods};
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Color};
+use ggez::mint::{Point2, Vector2};
+use ggez::{filesystem, Context, GameResult};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io::Write;
+
+// Used when --population is given without a size.
+pub const DEFAULT_POPULATION: usize = 300;
+
+// Used for breeding: the best few go to the next generation unchanged and the parents of the rest are
+// the best of a few picked at random. Every weight of a child changes with MUTATION_RATE by up to MUTATION_SIZE.
+pub const ELITE_COUNT: usize = 4;
+pub const TOURNAMENT_SIZE: usize = 3;
+pub const MUTATION_RATE: f64 = 0.1;
+pub const MUTATION_SIZE: f32 = 0.5;
+
+// How good a run is: one point for every tick flown and PIPE_FITNESS for every pipe passed.
+pub const PIPE_FITNESS: f32 = 100.0;
+// A generation that is still flying after this many ticks (5 minutes) is stopped.
+pub const MAX_GENERATION_TICKS: u64 = 5 * 60 * TICKS_PER_SECOND as u64;
+
+// The stats of every generation are written to /evolution.csv in the user data folder.
+pub const STATS_PATH: &str = "/evolution.csv";
+pub const STATS_HEADER: &str = "generation,best_fitness,average_fitness,best_score,ticks";
+
+// Used for drawing the population: the ones still flying are a bit see-through and the dead ones fade out in FADE_TIME seconds.
+pub const ALIVE_ALPHA: f32 = 0.6;
+pub const FADE_TIME: f32 = 0.75;
+// Up and Down change how many ticks are played every frame.
+pub const MAX_SPEED: usize = 64;
+pub const STATS_TOP: f32 = 20.0;
+pub const STATS_SPACING: f32 = 30.0;
+
+// How one generation did.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GenerationStats {
+    pub generation: usize,
+    pub best_fitness: f32,
+    pub average_fitness: f32,
+    pub best_score: i128,
+    pub ticks: u64,
+}
+impl GenerationStats {
+    pub fn csv_line(&self) -> String {
+        format!(
+            "{},{},{},{},{}",
+            self.generation, self.best_fitness, self.average_fitness, self.best_score, self.ticks
+        )
+    }
+}
+
+// A population of neural networks flying the same seeded course at the same time, one Ferris each.
+// When all of them have crashed, a genetic algorithm breeds the next generation from the ones that flew the furthest.
+pub struct Evolution {
+    pub networks: Vec<Network>,
+    pub sim: Simulation,
+    pub seed: u64,
+    pub generation: usize,
+    // The ticks played by the current generation and when each of its players crashed.
+    pub ticks: u64,
+    crashed_at: Vec<Option<u64>>,
+    events: Vec<GameEvent>,
+    rng: StdRng,
+}
+impl Evolution {
+    pub fn new(size: usize, seed: u64) -> Self {
+        let mut rng = StdRng::from_entropy();
+        let networks = (0..size).map(|_| Network::random(&mut rng)).collect();
+
+        let mut evolution = Self {
+            networks,
+            sim: Simulation::new(GameMode::Single),
+            seed,
+            generation: 1,
+            ticks: 0,
+            crashed_at: Vec::new(),
+            events: Vec::new(),
+            rng,
+        };
+        evolution.start_generation();
+
+        evolution
+    }
+
+    pub fn size(&self) -> usize {
+        self.networks.len()
+    }
+
+    pub fn alive(&self) -> usize {
+        self.sim
+            .players
+            .iter()
+            .filter(|player| player.alive)
+            .count()
+    }
+
+    // Puts the whole population at the start of the course.
+    fn start_generation(&mut self) {
+        self.sim = Simulation::new(GameMode::Single);
+        self.sim.set_population(self.networks.len());
+        self.sim.seed(self.seed);
+        self.sim.play_state = PlayState::Play;
+
+        self.ticks = 0;
+        self.crashed_at = vec![None; self.networks.len()];
+    }
+
+    // Plays a single tick. When the generation ends in it, the next one is bred and its stats are returned.
+    pub fn tick(&mut self) -> Option<GenerationStats> {
+        let flaps: Vec<bool> = self
+            .sim
+            .players
+            .iter()
+            .map(|player| {
+                player.alive && self.networks[player.index].flaps(&observe(&self.sim, player.index))
+            })
+            .collect();
+
+        let over = self.sim.step(&flaps, &mut self.events).over;
+        self.events.clear();
+        self.ticks += 1;
+
+        for player in self.sim.players.iter().filter(|player| !player.alive) {
+            self.crashed_at[player.index].get_or_insert(self.ticks);
+        }
+
+        if !over && self.ticks < MAX_GENERATION_TICKS {
+            return None;
+        }
+
+        let fitness: Vec<f32> = (0..self.networks.len())
+            .map(|index| {
+                let ticks = self.crashed_at[index].unwrap_or(self.ticks);
+                ticks as f32 + self.sim.teams[index].score as f32 * PIPE_FITNESS
+            })
+            .collect();
+        let stats = GenerationStats {
+            generation: self.generation,
+            best_fitness: fitness.iter().cloned().fold(0.0, f32::max),
+            average_fitness: fitness.iter().sum::<f32>() / fitness.len().max(1) as f32,
+            best_score: self
+                .sim
+                .teams
+                .iter()
+                .map(|team| team.score)
+                .max()
+                .unwrap_or(0),
+            ticks: self.ticks,
+        };
+
+        self.breed(&fitness);
+        self.generation += 1;
+        self.start_generation();
+
+        Some(stats)
+    }
+
+    // Replaces the networks with the next generation.
+    fn breed(&mut self, fitness: &[f32]) {
+        let mut ranked: Vec<usize> = (0..self.networks.len()).collect();
+        ranked.sort_by(|&a, &b| fitness[b].partial_cmp(&fitness[a]).unwrap());
+
+        let mut next: Vec<Network> = ranked
+            .iter()
+            .take(ELITE_COUNT)
+            .map(|&index| self.networks[index].clone())
+            .collect();
+        while next.len() < self.networks.len() {
+            let mother = self.pick_parent(fitness);
+            let father = self.pick_parent(fitness);
+
+            let mut child = self.networks[mother].crossover(&self.networks[father], &mut self.rng);
+            child.mutate(&mut self.rng, MUTATION_RATE, MUTATION_SIZE);
+            next.push(child);
+        }
+
+        self.networks = next;
+    }
+
+    // The fittest of a few networks picked at random.
+    fn pick_parent(&mut self, fitness: &[f32]) -> usize {
+        (0..TOURNAMENT_SIZE)
+            .map(|_| self.rng.gen_range(0..fitness.len()))
+            .max_by(|&a, &b| fitness[a].partial_cmp(&fitness[b]).unwrap())
+            .unwrap()
+    }
+}
+
+// The showcase started with --population: the population flies over the usual background, drawn in a single sprite batch,
+// with the stats of the generations in the corner.
+pub struct EvolutionState {
+    evolution: Evolution,
+    // How many ticks are played every frame.
+    speed: usize,
+    last: Option<GenerationStats>,
+    // How visible each Ferris is, so the dead ones can fade out.
+    alphas: Vec<f32>,
+    batch: SpriteBatch,
+    labels: Vec<Label>,
+    stats_file: Option<filesystem::File>,
+
+    assets: Assets,
+    locale: Locale,
+    parallax: Parallax,
+    viewport: Viewport,
+}
+impl EvolutionState {
+    pub fn new(ctx: &mut Context, size: usize, seed: u64) -> Self {
+        let assets = Assets::new(ctx).unwrap();
+        let locale = Locale::new(ctx, DEFAULT_LANGUAGE).unwrap();
+        let sheet = assets.image(&assets.animation("ferris").sheet).clone();
+
+        let created = filesystem::create(ctx, STATS_PATH).and_then(|mut file| {
+            writeln!(file, "{}", STATS_HEADER)?;
+            Ok(file)
+        });
+        let stats_file = match created {
+            Ok(file) => Some(file),
+            Err(error) => {
+                eprintln!("Could not create {}: {}", STATS_PATH, error);
+                None
+            }
+        };
+
+        Self {
+            evolution: Evolution::new(size, seed),
+            speed: 1,
+            last: None,
+            alphas: vec![ALIVE_ALPHA; size],
+            batch: SpriteBatch::new(sheet),
+            labels: (0..4)
+                .map(|_| Label::new(TextStyle::outlined(26.0)))
+                .collect(),
+            stats_file,
+
+            assets,
+            locale,
+            parallax: Parallax::new(),
+            viewport: Viewport::new(),
+        }
+    }
+
+    fn finish_generation(&mut self, stats: GenerationStats) {
+        eprintln!(
+            "Generation {}: best fitness {}, best score {}",
+            stats.generation, stats.best_fitness, stats.best_score
+        );
+        if let Some(file) = self.stats_file.as_mut() {
+            if let Err(error) = writeln!(file, "{}", stats.csv_line()).and_then(|_| file.flush()) {
+                eprintln!("Could not write {}: {}", STATS_PATH, error);
+            }
+        }
+
+        self.last = Some(stats);
+        self.alphas = vec![ALIVE_ALPHA; self.evolution.size()];
+    }
+
+    // Draws every Ferris with the current frame of its animation, all in one draw call.
+    fn draw_population(&mut self, ctx: &mut Context) -> GameResult {
+        let set = self.assets.animation("ferris");
+        let sheet = self.assets.image(&set.sheet);
+        let (width, height) = (sheet.width() as f32, sheet.height() as f32);
+
+        self.batch.clear();
+        for player in self.evolution.sim.players.iter() {
+            let alpha = self.alphas[player.index];
+            if alpha <= 0.0 {
+                continue;
+            }
+
+            let entity = &player.entity;
+            self.batch.add(
+                graphics::DrawParam::default()
+                    .src(set.frame_rect(entity.animator.frame(set), width, height))
+                    .dest(entity.position)
+                    .offset(Point2 { x: 0.5, y: 0.5 })
+                    .rotation(entity.rotation)
+                    .scale(Vector2 {
+                        x: 1.0 - STRETCH_AMOUNT * entity.stretch,
+                        y: 1.0 + STRETCH_AMOUNT * entity.stretch,
+                    })
+                    .color(Color::new(1.0, 1.0, 1.0, alpha)),
+            );
+        }
+
+        graphics::draw(ctx, &self.batch, graphics::DrawParam::default())
+    }
+
+    fn draw_stats(&mut self, ctx: &mut Context) -> GameResult {
+        let evolution = &self.evolution;
+        let mut lines = vec![
+            self.locale.format(
+                "evolution.generation",
+                &[("n", &evolution.generation.to_string())],
+            ),
+            self.locale.format(
+                "evolution.alive",
+                &[
+                    ("alive", &evolution.alive().to_string()),
+                    ("size", &evolution.size().to_string()),
+                ],
+            ),
+            self.locale
+                .format("evolution.speed", &[("speed", &self.speed.to_string())]),
+        ];
+        if let Some(last) = self.last {
+            lines.push(self.locale.format(
+                "evolution.last",
+                &[
+                    ("best", &format!("{:.0}", last.best_fitness)),
+                    ("average", &format!("{:.0}", last.average_fitness)),
+                    ("score", &last.best_score.to_string()),
+                ],
+            ));
+        }
+
+        let font = self.assets.font(self.locale.font());
+        for (index, (label, line)) in self.labels.iter_mut().zip(lines.iter()).enumerate() {
+            label.set(font, line);
+            label.draw(
+                ctx,
+                Point2 {
+                    x: HUD_MARGIN,
+                    y: STATS_TOP + index as f32 * STATS_SPACING,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+}
+impl EventHandler for EvolutionState {
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let seconds = TICK_SECONDS;
+
+        while ggez::timer::check_update_time(ctx, TICKS_PER_SECOND) {
+            for _ in 0..self.speed {
+                if let Some(stats) = self.evolution.tick() {
+                    self.finish_generation(stats);
+                }
+            }
+
+            self.parallax.update(self.evolution.sim.multiplier);
+            self.parallax.wrap(&self.assets);
+            for player in self.evolution.sim.players.iter_mut() {
+                let alpha = &mut self.alphas[player.index];
+                if !player.alive {
+                    *alpha -= ALIVE_ALPHA * seconds / FADE_TIME;
+                }
+                if *alpha > 0.0 {
+                    player.entity.animate(&self.assets, seconds);
+                }
+            }
+            for enemy in self.evolution.sim.enemies.iter_mut() {
+                enemy.animate(seconds);
+            }
+            for boost in self.evolution.sim.boosts.iter_mut() {
+                boost.animate(seconds);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        graphics::clear(ctx, self.assets.color("clear"));
+        self.parallax.draw(ctx, &self.assets, false)?;
+
+        self.draw_population(ctx)?;
+
+        let sim = &mut self.evolution.sim;
+        for pipe in sim.pipes.iter_mut() {
+            pipe.draw(ctx, &self.assets)?;
+        }
+        for enemy in sim.enemies.iter_mut() {
+            enemy.draw(ctx, &self.assets)?;
+        }
+        for boost in sim.boosts.iter_mut() {
+            boost.draw(ctx, &self.assets)?;
+        }
+
+        self.parallax.draw(ctx, &self.assets, true)?;
+        self.draw_stats(ctx)?;
+
+        // Covers the parts of the window outside of the game area.
+        self.viewport.draw_bars(ctx)?;
+
+        graphics::present(ctx)?;
+        std::thread::yield_now();
+
+        Ok(())
+    }
+
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        self.viewport.resize(ctx, width, height).unwrap();
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: KeyCode,
+        _keymods: KeyMods,
+        _repeat: bool,
+    ) {
+        match keycode {
+            KeyCode::Up => self.speed = (self.speed * 2).min(MAX_SPEED),
+            KeyCode::Down => self.speed = (self.speed / 2).max(1),
+            KeyCode::F11 => self.viewport.toggle_fullscreen(ctx).unwrap(),
+            KeyCode::Escape => event::quit(ctx),
+            _ => (),
+        }
+    }
+}
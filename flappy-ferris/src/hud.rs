@@ -0,0 +1,623 @@
+use crate::animation::Animator;
+use crate::assets::Assets;
+use crate::entities::{draw_animated, BoostType, BOOST_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::locale::Locale;
+use crate::players::player_tint;
+use ggez::graphics::{self, Color, Rect};
+use ggez::mint::{Point2, Vector2};
+use ggez::{Context, GameResult};
+
+// Used for drawing outlines and drop shadows behind text.
+pub const OUTLINE_WIDTH: f32 = 3.0;
+pub const SHADOW_OFFSET: f32 = 4.0;
+
+// Used for positioning the texts of the HUD.
+pub const HUD_MARGIN: f32 = 20.0;
+pub const SCORE_TOP: f32 = 40.0;
+pub const NEW_BEST_TOP: f32 = 160.0;
+pub const BEST_RUN_TOP: f32 = 200.0;
+pub const TITLE_TOP: f32 = 110.0;
+pub const HINT_TOP: f32 = 215.0;
+pub const WINNER_TOP: f32 = 270.0;
+pub const BEST_SCORE_TOP: f32 = 335.0;
+// Only the best few of an online race fit above the menu.
+pub const RESULTS_SHOWN: usize = 3;
+// The space left for the name of a player before their hearts.
+pub const PLAYER_NAME_WIDTH: f32 = 60.0;
+
+// Used for drawing the lifes as hearts. Above MAX_HEARTS the rest is shown as a number.
+pub const HEART_SIZE: f32 = 32.0;
+pub const HEART_SPACING: f32 = 6.0;
+pub const MAX_HEARTS: i128 = 5;
+
+// Used for drawing the icon and the timer bar of the active boost (under the speed indicator).
+pub const BOOST_ICON_SIZE: f32 = 48.0;
+pub const BOOST_ICON_TOP: f32 = 60.0;
+pub const BOOST_BAR_HEIGHT: f32 = 8.0;
+
+// The score grows by SCORE_POP_SCALE when a point is scored and shrinks back during SCORE_POP_TIME (in seconds).
+pub const SCORE_POP_TIME: f32 = 0.25;
+pub const SCORE_POP_SCALE: f32 = 0.35;
+
+// The point of the screen something is placed relative to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Anchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+impl Anchor {
+    // Where the top left corner of something with the given size goes, moved by the offset afterwards.
+    // Anchors on the right or bottom edges need negative offsets to move away from them.
+    pub fn position(&self, width: f32, height: f32, offset: Vector2<f32>) -> Point2<f32> {
+        let (x, y) = match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::TopCenter => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::BottomCenter => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        };
+
+        Point2 {
+            x: (SCREEN_WIDTH - width) * x + offset.x,
+            y: (SCREEN_HEIGHT - height) * y + offset.y,
+        }
+    }
+}
+
+// How a text looks. The outline and shadow are only drawn if they have a color.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TextStyle {
+    pub size: f32,
+    pub color: Color,
+    pub outline: Option<Color>,
+    pub shadow: Option<Color>,
+}
+impl TextStyle {
+    pub fn new(size: f32, color: Color) -> Self {
+        Self {
+            size,
+            color,
+            outline: None,
+            shadow: None,
+        }
+    }
+
+    pub fn with_outline(mut self, color: Color) -> Self {
+        self.outline = Some(color);
+        self
+    }
+
+    pub fn with_shadow(mut self, color: Color) -> Self {
+        self.shadow = Some(color);
+        self
+    }
+
+    // Black text with a white outline and a soft shadow, readable over both light and dark backgrounds.
+    pub fn outlined(size: f32) -> Self {
+        Self::new(size, Color::BLACK)
+            .with_outline(Color::WHITE)
+            .with_shadow(Color::from_rgba(0, 0, 0, 120))
+    }
+}
+
+// A text that is laid out once and kept until its value (or font) changes.
+pub struct Label {
+    pub style: TextStyle,
+    value: String,
+    font: Option<graphics::Font>,
+    text: Option<graphics::Text>,
+}
+impl Label {
+    pub fn new(style: TextStyle) -> Self {
+        Self {
+            style,
+            value: String::new(),
+            font: None,
+            text: None,
+        }
+    }
+
+    // Changes the shown value. Nothing is laid out again if neither the value nor the font changed.
+    pub fn set(&mut self, font: graphics::Font, value: &str) {
+        if self.text.is_some() && self.font == Some(font) && self.value == value {
+            return;
+        }
+
+        let mut text = graphics::Text::new(value);
+        text.set_font(font, graphics::PxScale::from(self.style.size));
+
+        self.value = value.to_string();
+        self.font = Some(font);
+        self.text = Some(text);
+    }
+
+    pub fn width(&self, ctx: &mut Context) -> f32 {
+        self.text.as_ref().map_or(0.0, |text| text.width(ctx))
+    }
+
+    pub fn height(&self, ctx: &mut Context) -> f32 {
+        self.text.as_ref().map_or(0.0, |text| text.height(ctx))
+    }
+
+    // Draws the text with its top left corner at the given position, with the shadow and outline behind it.
+    pub fn draw(&self, ctx: &mut Context, position: Point2<f32>) -> GameResult {
+        self.draw_scaled(ctx, position, 1.0)
+    }
+
+    // Same as draw, but the text (with its outline and shadow) is scaled from its top left corner.
+    pub fn draw_scaled(&self, ctx: &mut Context, position: Point2<f32>, scale: f32) -> GameResult {
+        let text = match self.text.as_ref() {
+            Some(text) => text,
+            None => return Ok(()),
+        };
+
+        let draw_at = |ctx: &mut Context, x: f32, y: f32, color: Color| {
+            graphics::draw(
+                ctx,
+                text,
+                graphics::DrawParam::default()
+                    .dest(Point2 {
+                        x: position.x + x * scale,
+                        y: position.y + y * scale,
+                    })
+                    .scale(Vector2 { x: scale, y: scale })
+                    .color(color),
+            )
+        };
+
+        if let Some(shadow) = self.style.shadow {
+            draw_at(ctx, SHADOW_OFFSET, SHADOW_OFFSET, shadow)?;
+        }
+
+        // The outline is the text drawn moved in all eight directions.
+        if let Some(outline) = self.style.outline {
+            for x in [-OUTLINE_WIDTH, 0.0, OUTLINE_WIDTH] {
+                for y in [-OUTLINE_WIDTH, 0.0, OUTLINE_WIDTH] {
+                    if x != 0.0 || y != 0.0 {
+                        draw_at(ctx, x, y, outline)?;
+                    }
+                }
+            }
+        }
+
+        draw_at(ctx, 0.0, 0.0, self.style.color)
+    }
+
+    // Draws the text placed relative to a point of the screen.
+    pub fn draw_anchored(
+        &self,
+        ctx: &mut Context,
+        anchor: Anchor,
+        offset: Vector2<f32>,
+    ) -> GameResult {
+        let position = anchor.position(self.width(ctx), self.height(ctx), offset);
+        self.draw(ctx, position)
+    }
+}
+
+// Everything the HUD shows about a team (one or more players sharing their lifes and score).
+pub struct TeamStats {
+    pub lifes: i128,
+    pub score: i128,
+}
+
+// Everything the HUD shows while playing.
+pub struct Stats {
+    pub teams: Vec<TeamStats>,
+    pub best_score: i128,
+    pub multiplier: f32,
+    // The active boost and how much of its time is left (from 1.0 down to 0.0).
+    pub boost: Option<(BoostType, f32)>,
+    // How many points the player is ahead of the best run on the course at the same moment (negative if behind).
+    pub best_run: Option<i128>,
+}
+
+// Everything shown over the game: the lifes, scores, speed and active boost while playing
+// and the title with the best score (or the winner) on the start and game over screens.
+pub struct Hud {
+    title: Label,
+    hint: Label,
+    // One of each for every team.
+    names: Vec<Label>,
+    extra_lifes: Vec<Label>,
+    scores: Vec<Label>,
+    new_best: Label,
+    best_run: Label,
+    speed: Label,
+    best_score: Label,
+    winner: Label,
+    results: Label,
+    // Time left (in seconds) of the score pop animation.
+    score_pop: f32,
+}
+impl Hud {
+    pub fn new() -> Self {
+        Self {
+            title: Label::new(TextStyle::new(90.0, Color::WHITE).with_outline(Color::BLACK)),
+            hint: Label::new(TextStyle::new(36.0, Color::WHITE).with_outline(Color::BLACK)),
+            names: Vec::new(),
+            extra_lifes: Vec::new(),
+            scores: Vec::new(),
+            new_best: Label::new(
+                TextStyle::new(30.0, Color::from_rgb(255, 215, 0))
+                    .with_outline(Color::BLACK)
+                    .with_shadow(Color::from_rgba(0, 0, 0, 120)),
+            ),
+            best_run: Label::new(TextStyle::outlined(30.0)),
+            speed: Label::new(TextStyle::outlined(30.0)),
+            best_score: Label::new(TextStyle::outlined(50.0)),
+            winner: Label::new(TextStyle::outlined(50.0)),
+            results: Label::new(TextStyle::outlined(32.0)),
+            score_pop: 0.0,
+        }
+    }
+
+    // Makes the scores pop (when a point is scored).
+    pub fn pop_score(&mut self) {
+        self.score_pop = SCORE_POP_TIME;
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        self.score_pop = (self.score_pop - delta).max(0.0);
+    }
+
+    // Displays the lifes, scores, speed and active boost during the game.
+    pub fn draw_stats(
+        &mut self,
+        ctx: &mut Context,
+        assets: &Assets,
+        locale: &Locale,
+        stats: &Stats,
+    ) -> GameResult {
+        let font = assets.font(locale.font());
+        let count = stats.teams.len();
+
+        for (index, team) in stats.teams.iter().enumerate() {
+            // With more than one team (one player each), every one has a row of hearts starting with their name and a score in their color.
+            let mut position = Point2 {
+                x: HUD_MARGIN,
+                y: HUD_MARGIN + index as f32 * (HEART_SIZE + HEART_SPACING),
+            };
+            let style = if count > 1 {
+                let name = label(&mut self.names, index);
+                name.style = player_style(30.0, index);
+                name.set(
+                    font,
+                    &locale.format("player", &[("n", &(index + 1).to_string())]),
+                );
+                name.draw(ctx, position)?;
+                position.x += PLAYER_NAME_WIDTH;
+
+                player_style(100.0, index)
+            } else {
+                TextStyle::outlined(100.0)
+            };
+
+            self.draw_lifes(ctx, assets, locale, index, team.lifes, position)?;
+
+            // The scores are spread evenly over the top of the screen and grow from their center when they pop.
+            let scale = 1.0 + SCORE_POP_SCALE * (self.score_pop / SCORE_POP_TIME);
+            let score = label(&mut self.scores, index);
+            score.style = style;
+            score.set(font, &team.score.to_string());
+
+            let (width, height) = (score.width(ctx), score.height(ctx));
+            let center = SCREEN_WIDTH * (index + 1) as f32 / (count + 1) as f32;
+            let position = Anchor::TopCenter.position(
+                width * scale,
+                height * scale,
+                Vector2 {
+                    x: center - SCREEN_WIDTH / 2.0,
+                    y: SCORE_TOP - height * (scale - 1.0) / 2.0,
+                },
+            );
+            score.draw_scaled(ctx, position, scale)?;
+        }
+
+        // Only shown when there was a previous best score to beat.
+        if stats.best_score > 0 && stats.teams.iter().any(|team| team.score > stats.best_score) {
+            self.new_best.set(font, &locale.get("new_best"));
+            self.new_best.draw_anchored(
+                ctx,
+                Anchor::TopCenter,
+                Vector2 {
+                    x: 0.0,
+                    y: NEW_BEST_TOP,
+                },
+            )?;
+        }
+
+        // Green while ahead of the best run and red while behind it.
+        if let Some(difference) = stats.best_run {
+            let (text, color) = if difference > 0 {
+                (
+                    locale.plural("best_run.ahead", difference),
+                    Color::from_rgb(90, 230, 110),
+                )
+            } else if difference < 0 {
+                (
+                    locale.plural("best_run.behind", -difference),
+                    Color::from_rgb(230, 60, 60),
+                )
+            } else {
+                (locale.get("best_run.even"), Color::WHITE)
+            };
+
+            self.best_run.style.color = color;
+            self.best_run.set(font, &text);
+            self.best_run.draw_anchored(
+                ctx,
+                Anchor::TopCenter,
+                Vector2 {
+                    x: 0.0,
+                    y: BEST_RUN_TOP,
+                },
+            )?;
+        }
+
+        let speed = format!("{:.1}", stats.multiplier);
+        self.speed
+            .set(font, &locale.format("speed", &[("speed", &speed)]));
+        self.speed.style.color = match stats.boost {
+            Some((boost, _)) if boost != BoostType::BonusLife => boost.color(),
+            _ => Color::BLACK,
+        };
+        self.speed.draw_anchored(
+            ctx,
+            Anchor::TopRight,
+            Vector2 {
+                x: -HUD_MARGIN,
+                y: HUD_MARGIN,
+            },
+        )?;
+
+        if let Some((boost, time_left)) = stats.boost {
+            draw_boost(ctx, assets, boost, time_left)?;
+        }
+
+        Ok(())
+    }
+
+    // Draws a heart for each life of a team, starting at the given position. The lifes that do not fit are shown as "+N".
+    fn draw_lifes(
+        &mut self,
+        ctx: &mut Context,
+        assets: &Assets,
+        locale: &Locale,
+        team: usize,
+        lifes: i128,
+        position: Point2<f32>,
+    ) -> GameResult {
+        let heart = assets.image("heart");
+        let scale = HEART_SIZE / heart.width() as f32;
+        let hearts = lifes.clamp(0, MAX_HEARTS);
+
+        for index in 0..hearts {
+            let position = Point2 {
+                x: position.x + index as f32 * (HEART_SIZE + HEART_SPACING),
+                y: position.y,
+            };
+            graphics::draw(
+                ctx,
+                heart,
+                graphics::DrawParam::default()
+                    .dest(position)
+                    .scale(Vector2 { x: scale, y: scale }),
+            )?;
+        }
+
+        if lifes > MAX_HEARTS {
+            let extra_lifes = label(&mut self.extra_lifes, team);
+            extra_lifes.set(
+                assets.font(locale.font()),
+                &locale.plural("extra_lifes", lifes - MAX_HEARTS),
+            );
+            extra_lifes.draw(
+                ctx,
+                Point2 {
+                    x: position.x + hearts as f32 * (HEART_SIZE + HEART_SPACING),
+                    y: position.y,
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Displays the name of the game on the start screen or the game over message, with a hint how to (re)start under it.
+    pub fn draw_title(
+        &mut self,
+        ctx: &mut Context,
+        assets: &Assets,
+        locale: &Locale,
+        game_over: bool,
+        online: bool,
+    ) -> GameResult {
+        let font = assets.font(locale.font());
+
+        // Online races are started by the lobby server instead of the player.
+        let (title, hint, color) = match (game_over, online) {
+            (true, false) => ("game_over", "restart_hint", Color::from_rgb(230, 20, 20)),
+            (true, true) => ("game_over", "net.next_race", Color::from_rgb(230, 20, 20)),
+            (false, false) => ("title", "start_hint", Color::WHITE),
+            (false, true) => ("title", "net.waiting", Color::WHITE),
+        };
+        self.title.style.color = color;
+        self.hint.style.color = color;
+
+        self.title.set(font, &locale.get(title));
+        self.hint.set(font, &locale.get(hint));
+
+        self.title.draw_anchored(
+            ctx,
+            Anchor::TopCenter,
+            Vector2 {
+                x: 0.0,
+                y: TITLE_TOP,
+            },
+        )?;
+        self.hint.draw_anchored(
+            ctx,
+            Anchor::TopCenter,
+            Vector2 {
+                x: 0.0,
+                y: HINT_TOP,
+            },
+        )
+    }
+
+    // Displays the best score on the game over screen, above the menu.
+    pub fn draw_best_score(
+        &mut self,
+        ctx: &mut Context,
+        assets: &Assets,
+        locale: &Locale,
+        best_score: i128,
+    ) -> GameResult {
+        self.best_score.set(
+            assets.font(locale.font()),
+            &locale.format("best_score", &[("score", &best_score.to_string())]),
+        );
+        self.best_score.draw_anchored(
+            ctx,
+            Anchor::TopCenter,
+            Vector2 {
+                x: 0.0,
+                y: BEST_SCORE_TOP,
+            },
+        )
+    }
+
+    // Displays the best scores of the last online race instead of the best score.
+    pub fn draw_results(
+        &mut self,
+        ctx: &mut Context,
+        assets: &Assets,
+        locale: &Locale,
+        results: &[(String, i128)],
+    ) -> GameResult {
+        let lines: Vec<String> = results
+            .iter()
+            .take(RESULTS_SHOWN)
+            .enumerate()
+            .map(|(place, (name, score))| {
+                locale.format(
+                    "net.place",
+                    &[
+                        ("place", &(place + 1).to_string()),
+                        ("name", name),
+                        ("score", &score.to_string()),
+                    ],
+                )
+            })
+            .collect();
+
+        self.results
+            .set(assets.font(locale.font()), &lines.join("\n"));
+        self.results.draw_anchored(
+            ctx,
+            Anchor::TopCenter,
+            Vector2 {
+                x: 0.0,
+                y: WINNER_TOP,
+            },
+        )
+    }
+
+    // Displays who won the last game with more than one player (in their color), or a draw.
+    pub fn draw_winner(
+        &mut self,
+        ctx: &mut Context,
+        assets: &Assets,
+        locale: &Locale,
+        winner: Option<usize>,
+    ) -> GameResult {
+        let text = match winner {
+            Some(index) => {
+                self.winner.style = player_style(50.0, index);
+                locale.format("winner", &[("n", &(index + 1).to_string())])
+            }
+            None => {
+                self.winner.style = TextStyle::outlined(50.0);
+                locale.get("draw")
+            }
+        };
+
+        self.winner.set(assets.font(locale.font()), &text);
+        self.winner.draw_anchored(
+            ctx,
+            Anchor::TopCenter,
+            Vector2 {
+                x: 0.0,
+                y: WINNER_TOP,
+            },
+        )
+    }
+}
+
+// The label of a player from a list that grows when there are more players than before.
+fn label(labels: &mut Vec<Label>, index: usize) -> &mut Label {
+    while labels.len() <= index {
+        labels.push(Label::new(TextStyle::outlined(30.0)));
+    }
+
+    &mut labels[index]
+}
+
+// Text in the color of a player, outlined so even the lightest colors are readable.
+fn player_style(size: f32, player: usize) -> TextStyle {
+    TextStyle::new(size, player_tint(player))
+        .with_outline(Color::BLACK)
+        .with_shadow(Color::from_rgba(0, 0, 0, 120))
+}
+
+// Draws the icon of the active boost in the top right corner with a bar under it that empties as its time runs out.
+fn draw_boost(ctx: &mut Context, assets: &Assets, boost: BoostType, time_left: f32) -> GameResult {
+    let icon = Rect::new(
+        SCREEN_WIDTH - HUD_MARGIN - BOOST_ICON_SIZE,
+        BOOST_ICON_TOP + HUD_MARGIN,
+        BOOST_ICON_SIZE,
+        BOOST_ICON_SIZE,
+    );
+    let scale = BOOST_ICON_SIZE / BOOST_WIDTH;
+
+    draw_animated(
+        ctx,
+        assets,
+        "boosts",
+        &Animator::new(boost.clip()),
+        graphics::DrawParam::default()
+            .dest(Point2 {
+                x: icon.x,
+                y: icon.y,
+            })
+            .scale(Vector2 { x: scale, y: scale }),
+    )?;
+
+    let background = Rect::new(icon.x, icon.bottom() + 4.0, icon.w, BOOST_BAR_HEIGHT);
+    let fill = Rect {
+        w: background.w * time_left.clamp(0.0, 1.0),
+        ..background
+    };
+
+    let mut bar = graphics::MeshBuilder::new();
+    bar.rectangle(
+        graphics::DrawMode::fill(),
+        background,
+        Color::from_rgba(0, 0, 0, 120),
+    )?;
+    // A rectangle without a width can not be built.
+    if fill.w >= 1.0 {
+        bar.rectangle(graphics::DrawMode::fill(), fill, boost.color())?;
+    }
+    let bar = bar.build(ctx)?;
+
+    graphics::draw(ctx, &bar, graphics::DrawParam::default())
+}
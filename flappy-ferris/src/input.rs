@@ -0,0 +1,47 @@
+use ggez::event::winit_event::TouchPhase;
+use ggez::event::{KeyCode, MouseButton};
+use ggez::input::{keyboard, mouse};
+use ggez::Context;
+
+// Everything that can make Ferris flap: a key, the left mouse button (or trackpad click) and touches.
+pub struct FlapInput {
+    pub key: KeyCode,
+    // Only one player can use the mouse and touches, the others have just a key.
+    pub pointer: bool,
+    pub touch_held: bool,
+    // Set when a click was meant for a menu button, so it is not counted as a flap until the button is released.
+    pub mouse_blocked: bool,
+}
+impl FlapInput {
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            pointer: true,
+            touch_held: false,
+            mouse_blocked: false,
+        }
+    }
+
+    pub fn key_only(key: KeyCode) -> Self {
+        Self {
+            pointer: false,
+            ..FlapInput::new(key)
+        }
+    }
+
+    // ggez does not keep track of touches like it does for keys and buttons, so we store it ourselves.
+    pub fn touch(&mut self, phase: TouchPhase) {
+        self.touch_held = match phase {
+            TouchPhase::Started | TouchPhase::Moved => true,
+            TouchPhase::Ended | TouchPhase::Cancelled => false,
+        };
+    }
+
+    // Checks if any of the sources is held down at the moment.
+    pub fn is_held(&self, ctx: &Context) -> bool {
+        let pointer_held = (mouse::button_pressed(ctx, MouseButton::Left) && !self.mouse_blocked)
+            || self.touch_held;
+
+        keyboard::is_key_pressed(ctx, self.key) || (self.pointer && pointer_held)
+    }
+}
@@ -1,2 +1,24 @@
+pub mod agent;
+pub mod animation;
 pub mod assets;
+pub mod audio;
+pub mod autopilot;
+pub mod debug;
+pub mod effects;
 pub mod entities;
+pub mod env;
+pub mod events;
+pub mod evolution;
+pub mod hud;
+pub mod input;
+pub mod locale;
+pub mod menu;
+pub mod net;
+pub mod neural;
+pub mod parallax;
+pub mod particles;
+pub mod players;
+pub mod replay;
+pub mod sim;
+pub mod viewport;
+pub mod watcher;
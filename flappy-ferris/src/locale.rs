@@ -0,0 +1,223 @@
+use crate::assets::read_resource;
+use ggez::{filesystem, Context, GameError, GameResult};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// Every file in here named <language>.lang is a string table for that language.
+pub const LANGUAGES_PATH: &str = "/lang";
+pub const LANGUAGE_EXTENSION: &str = "lang";
+// The language used at start and for every key missing from another language.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+// The forms a counted word can take. Each language picks one of them for a number with its plural rule.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Plural {
+    Zero,
+    One,
+    Few,
+    Many,
+    Other,
+}
+impl Plural {
+    // The plural rule of the given language.
+    pub fn of(language: &str, count: i128) -> Self {
+        match language {
+            // English and Bulgarian only tell one thing apart from all others.
+            "en" | "bg" => {
+                if count == 1 {
+                    Plural::One
+                } else {
+                    Plural::Other
+                }
+            }
+            // Languages without a rule of their own use the one of the default language.
+            _ => Plural::of(DEFAULT_LANGUAGE, count),
+        }
+    }
+
+    // The suffix of the keys with this form, e.g. "lifes.one".
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Plural::Zero => "zero",
+            Plural::One => "one",
+            Plural::Few => "few",
+            Plural::Many => "many",
+            Plural::Other => "other",
+        }
+    }
+}
+
+// Parses a string table, for example:
+//   @name = English
+//   best_score = Best score: {score}
+//   extra_lifes.one = +{n} life
+// Keys starting with '@' describe the language itself instead of being shown.
+// Empty lines and lines starting with '#' are skipped and all invalid lines are returned together.
+pub fn parse_strings(source: &str) -> Result<HashMap<String, String>, Vec<String>> {
+    let mut strings = HashMap::new();
+    let mut errors = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                strings.insert(key.trim().to_string(), value.trim().to_string());
+            }
+            _ => errors.push(format!(
+                "line {}: expected '<key> = <text>', found '{}'",
+                number + 1,
+                line
+            )),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(strings)
+    } else {
+        Err(errors)
+    }
+}
+
+// Replaces every "{name}" in the text with its value.
+pub fn fill(text: &str, values: &[(&str, &str)]) -> String {
+    values.iter().fold(text.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+// The texts of the current language. Keys missing from it are taken from the default language,
+// and keys missing from both are shown as they are, so a missing translation is easy to spot.
+pub struct Locale {
+    pub language: String,
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+impl Locale {
+    pub fn new(ctx: &mut Context, language: &str) -> GameResult<Self> {
+        let fallback = Locale::read_strings(ctx, DEFAULT_LANGUAGE)?;
+        let strings = if language == DEFAULT_LANGUAGE {
+            fallback.clone()
+        } else {
+            Locale::read_strings(ctx, language)?
+        };
+
+        Ok(Self {
+            language: language.to_string(),
+            strings,
+            fallback,
+        })
+    }
+
+    // Reads the string tables straight from the resources folder on disk, for front-ends without a ggez context
+    // (like the terminal one).
+    pub fn from_folder(resources: &Path, language: &str) -> Result<Self, String> {
+        let read = |language: &str| {
+            let path = resources.join(Locale::path(language).trim_start_matches('/'));
+            let source = fs::read_to_string(&path)
+                .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+
+            parse_strings(&source).map_err(|errors| {
+                format!(
+                    "Invalid string table {}:\n{}",
+                    path.display(),
+                    errors.join("\n")
+                )
+            })
+        };
+
+        let fallback = read(DEFAULT_LANGUAGE)?;
+        let strings = if language == DEFAULT_LANGUAGE {
+            fallback.clone()
+        } else {
+            read(language)?
+        };
+
+        Ok(Self {
+            language: language.to_string(),
+            strings,
+            fallback,
+        })
+    }
+
+    fn read_strings(ctx: &mut Context, language: &str) -> GameResult<HashMap<String, String>> {
+        let path = Locale::path(language);
+        let source = read_resource(ctx, &path)?;
+
+        parse_strings(&source).map_err(|errors| {
+            GameError::ResourceLoadError(format!(
+                "Invalid string table {}:\n{}",
+                path,
+                errors.join("\n")
+            ))
+        })
+    }
+
+    // The string table file of a language (inside the resources folder).
+    pub fn path(language: &str) -> String {
+        format!("{}/{}.{}", LANGUAGES_PATH, language, LANGUAGE_EXTENSION)
+    }
+
+    // Returns all languages with a string table, starting with the default one.
+    pub fn languages(ctx: &mut Context) -> Vec<String> {
+        let mut languages: Vec<String> = match filesystem::read_dir(ctx, LANGUAGES_PATH) {
+            Ok(files) => files
+                .filter(|file| {
+                    file.extension().and_then(|extension| extension.to_str())
+                        == Some(LANGUAGE_EXTENSION)
+                })
+                .filter_map(|file| Some(file.file_stem()?.to_str()?.to_string()))
+                .filter(|language| language != DEFAULT_LANGUAGE)
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+
+        languages.sort();
+        languages.dedup();
+        languages.insert(0, DEFAULT_LANGUAGE.to_string());
+
+        languages
+    }
+
+    // Looks up a key in the current language and then in the default one.
+    pub fn find(&self, key: &str) -> Option<&str> {
+        self.strings
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map(|text| text.as_str())
+    }
+
+    pub fn get(&self, key: &str) -> String {
+        self.find(key).unwrap_or(key).to_string()
+    }
+
+    // The text of a key with its "{name}" placeholders filled in.
+    pub fn format(&self, key: &str, values: &[(&str, &str)]) -> String {
+        fill(&self.get(key), values)
+    }
+
+    // The form of a key that matches the count (e.g. "extra_lifes.one" or "extra_lifes.other"), with "{n}" replaced by it.
+    pub fn plural(&self, key: &str, count: i128) -> String {
+        let form = Plural::of(&self.language, count);
+        let text = self
+            .find(&format!("{}.{}", key, form.suffix()))
+            .or_else(|| self.find(&format!("{}.{}", key, Plural::Other.suffix())))
+            .unwrap_or(key);
+
+        fill(text, &[("n", &count.to_string())])
+    }
+
+    // The name of the language in itself, e.g. "Български".
+    pub fn name(&self) -> String {
+        self.get("@name")
+    }
+
+    // The id (in the asset manifest) of the font that has all letters of the language.
+    pub fn font(&self) -> &str {
+        self.find("@font").unwrap_or("main")
+    }
+}
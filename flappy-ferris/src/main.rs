@@ -1,427 +1,1014 @@
+use flappy_ferris::agent::{run_headless, AgentIo, DEFAULT_FLAP, DEFAULT_TIMEOUT_MS};
 use flappy_ferris::assets::Assets;
+use flappy_ferris::audio::{Channel, Mixer, MusicTrack, VOLUME_STEP};
+use flappy_ferris::autopilot::Autopilot;
+use flappy_ferris::debug::{draw_outline, draw_vector, DebugOverlay};
+use flappy_ferris::effects::{Effects, DEATH_SHAKE, FLASH_TIME, HIT_SHAKE, HIT_STOP_TIME};
 use flappy_ferris::entities::{
-    BoostEntity, BoostType, EnemyEntity, PipeEntity, PlayState, PlayerEntity, BOOST_WIDTH,
-    ENEMY_WIDTH, PIPE_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH,
+    PlayState, BOOST_SPEED, ENEMY_SPEED, FERRIS_WIDTH, PIPE_SPEED, SCREEN_HEIGHT, SCREEN_WIDTH,
 };
-use ggez::audio::SoundSource;
+use flappy_ferris::events::GameEvent;
+use flappy_ferris::evolution::{EvolutionState, DEFAULT_POPULATION, ELITE_COUNT};
+use flappy_ferris::hud::{Hud, Stats, TeamStats};
+use flappy_ferris::locale::{Locale, DEFAULT_LANGUAGE, LANGUAGES_PATH};
+use flappy_ferris::menu::{Menu, Setting};
+use flappy_ferris::net::{Ghost, NetClient, NetEvent, DEFAULT_PORT};
+use flappy_ferris::parallax::Parallax;
+use flappy_ferris::particles::{Emitter, ParticleSystem};
+use flappy_ferris::players::GameMode;
+use flappy_ferris::replay::{Replay, ReplayGhost};
+use flappy_ferris::sim::{Simulation, BOOST_DURATION, TICKS_PER_SECOND, TICK_SECONDS};
+use flappy_ferris::viewport::Viewport;
+use flappy_ferris::watcher::ResourceWatcher;
 use ggez::conf::{Backend, Conf, ModuleConf, WindowMode, WindowSetup};
-use ggez::mint::Point2;
+use ggez::event::winit_event::TouchPhase;
+use ggez::event::{KeyCode, KeyMods, MouseButton};
+use ggez::mint::{Point2, Vector2};
 use ggez::ContextBuilder;
 use ggez::{event, event::EventHandler, graphics, Context, GameResult};
-use rand::rngs::ThreadRng;
-use rand::Rng;
-use std::collections::VecDeque;
 use std::path;
-
-pub const BOOST_DURATION: f32 = 10000000000.0;
+use std::time::Duration;
 
 // The struct of the game.
 pub struct MainState {
-    player: PlayerEntity,
-    pipes: VecDeque<PipeEntity>,
-    enemies: VecDeque<EnemyEntity>,
-    boosts: VecDeque<BoostEntity>,
-
-    time_until_next_pipe: f32,
-    time_until_next_enemy: f32,
-    time_until_next_boost: f32,
-
-    boost_duration: f32,
-    multiplier: f32,
-
-    hit_pipe: bool,
-    hit_enemy: bool,
-    has_boost: bool,
+    // The players, their teams and the course they fly through.
+    sim: Simulation,
 
-    play_state: PlayState,
-
-    lifes: i128,
-    score: i128,
     best_score: i128,
 
     assets: Assets,
-
-    rng: ThreadRng,
+    themes: Vec<String>,
+    locale: Locale,
+    languages: Vec<String>,
+
+    viewport: Viewport,
+    mixer: Mixer,
+    menu: Menu,
+    hud: Hud,
+    parallax: Parallax,
+    particles: ParticleSystem,
+    effects: Effects,
+    debug: DebugOverlay,
+    // Only used in the development mode (started with --dev) for reloading changed resources.
+    watcher: Option<ResourceWatcher>,
+
+    // Only used in online races (started with --connect).
+    net: Option<NetClient>,
+    ghosts: Vec<Ghost>,
+    // The number of ticks played in the current race and the scores of the last one.
+    race_tick: u64,
+    race_results: Vec<(String, i128)>,
+
+    // Everything that happened during the current frame.
+    events: Vec<GameEvent>,
+
+    // The course of every game when started with --seed. The runs on it are recorded and the best one is replayed next to the player.
+    seed: Option<u64>,
+    recording: Option<Replay>,
+    best_run: Option<ReplayGhost>,
+
+    // Flies the players in the attract mode on the start screen and the first player while playing if the assist is on.
+    autopilot: Autopilot,
+    assist: bool,
+
+    // Only used when a bot plays the first player over stdin and stdout (started with --agent-stdio).
+    agent: Option<AgentIo>,
 }
 impl MainState {
-    pub fn new(ctx: &mut Context) -> Self {
+    pub fn new(
+        ctx: &mut Context,
+        watcher: Option<ResourceWatcher>,
+        net: Option<NetClient>,
+        seed: Option<u64>,
+        agent: Option<AgentIo>,
+    ) -> Self {
         let assets = Assets::new(ctx).unwrap();
+        let themes = Assets::themes(ctx);
+        let locale = Locale::new(ctx, DEFAULT_LANGUAGE).unwrap();
+        let languages = Locale::languages(ctx);
+        graphics::set_window_title(ctx, &locale.get("title"));
+
+        // Online races are always played alone on each computer, so the mode can not be changed,
+        // and by the players themselves, so the autopilot can not fly them.
+        let mut settings = vec![
+            Setting::Mode,
+            Setting::Language,
+            Setting::Theme,
+            Setting::ScreenShake,
+            Setting::HitStop,
+            Setting::Flash,
+            Setting::Tint,
+            Setting::Autopilot,
+        ];
+        if net.is_some() {
+            settings.retain(|setting| *setting != Setting::Mode && *setting != Setting::Autopilot);
+        }
 
         Self {
-            player: PlayerEntity::new(),
-            pipes: VecDeque::new(),
-            enemies: VecDeque::new(),
-            boosts: VecDeque::new(),
+            sim: Simulation::new(GameMode::Single),
 
-            // Time until each new entity is stored in ns and each frame's length is subtracted.
-            time_until_next_pipe: 1000000000.0,
-            time_until_next_enemy: 10000000000.0,
-            time_until_next_boost: 10000000000.0,
-
-            boost_duration: 0.0,
-            multiplier: 1.0,
-
-            hit_pipe: false,
-            hit_enemy: false,
-            has_boost: false,
-
-            play_state: PlayState::StartScreen,
-
-            lifes: 1,
-            score: 0,
             best_score: 0,
 
             assets: assets,
-
-            rng: rand::thread_rng(),
+            themes: themes,
+            locale: locale,
+            languages: languages,
+
+            viewport: Viewport::new(),
+            mixer: Mixer::new(),
+            menu: Menu::new(settings),
+            hud: Hud::new(),
+            parallax: Parallax::new(),
+            particles: ParticleSystem::new(ctx).unwrap(),
+            effects: Effects::new(),
+            debug: DebugOverlay::new(),
+            watcher: watcher,
+
+            net: net,
+            ghosts: Vec::new(),
+            race_tick: 0,
+            race_results: Vec::new(),
+
+            events: Vec::new(),
+
+            seed: seed,
+            recording: None,
+            best_run: None,
+
+            autopilot: Autopilot::new(),
+            assist: false,
+
+            agent: agent,
         }
     }
 
     // Resets all fields after a given game ends.
     fn restart(&mut self) {
-        self.player = PlayerEntity::new();
-        self.pipes = VecDeque::new();
-        self.enemies = VecDeque::new();
-        self.boosts = VecDeque::new();
-
-        self.time_until_next_pipe = 1000000000.0;
-        self.time_until_next_enemy = 10000000000.0;
-        self.time_until_next_boost = 10000000000.0;
-
-        self.boost_duration = 0.0;
-        self.multiplier = 1.0;
-
-        self.hit_pipe = false;
-        self.hit_enemy = false;
-        self.has_boost = false;
-
-        // In order to actually display the GameOver logo instead of the StartScreen one.
-        self.play_state = PlayState::Dead;
-
         // Before resetting the scores, we change the best score if needed.
         self.swap_scores();
-        self.lifes = 1;
-        self.score = 0;
+        self.sim.restart();
+
+        self.recording = None;
+        self.best_run = None;
     }
 
     // Updates the scores after a given game ends.
     fn swap_scores(&mut self) {
-        if self.score > self.best_score {
-            self.best_score = self.score;
+        for team in self.sim.teams.iter() {
+            if team.score > self.best_score {
+                self.best_score = team.score;
+            }
         }
     }
 
-    // Checks if the player lost the current game.
-    fn is_over(&mut self) -> bool {
-        if ((self.player.hits_ground()) || self.hit_enemy || self.hit_pipe)
-            && self.play_state.is_playing()
-        {
-            return true;
+    // Loads again the resources that changed on disk (only in the development mode).
+    fn reload_resources(&mut self, ctx: &mut Context, delta: f32) {
+        let changed = match self.watcher.as_mut() {
+            Some(watcher) => watcher.changed_files(delta),
+            None => return,
+        };
+
+        if changed.is_empty() {
+            return;
         }
 
-        false
+        // A broken file should not stop the game, the previous version of the resource is kept instead.
+        match self.assets.reload(ctx, &changed) {
+            Ok(reloaded) => {
+                eprintln!("Reloaded: {}", reloaded.join(", "));
+                self.mixer.assets_reloaded(&reloaded);
+            }
+            Err(error) => eprintln!("Could not reload resources: {}", error),
+        }
+
+        self.themes = Assets::themes(ctx);
+
+        // The string tables are not in the asset manifest, so they are read again separately.
+        if changed.iter().any(|path| path.starts_with(LANGUAGES_PATH)) {
+            let language = self.locale.language.clone();
+            self.set_language(ctx, &language);
+            self.languages = Locale::languages(ctx);
+        }
     }
 
-    // Displays the current score and lifes left during the game.
-    fn draw_stats(&mut self, ctx: &mut Context) {
-        let font = graphics::Font::new(ctx, "/FlappyBird.ttf").unwrap();
-
-        // Displays the lifes available
-        let mut scores = graphics::Text::new(format!("Lifes available: {}", self.lifes));
-        scores.set_font(font, graphics::PxScale::from(30.0));
-        graphics::draw(
-            ctx,
-            &scores,
-            graphics::DrawParam::default()
-                .dest(Point2 {
-                    x: (SCREEN_WIDTH - scores.width(ctx)) / 2.0,
-                    y: (SCREEN_HEIGHT - scores.height(ctx)) / 8.0,
-                })
-                .color(graphics::Color::BLACK),
-        )
-        .unwrap();
+    // The text on the menu button of the given setting.
+    fn setting_label(&self, setting: Setting) -> String {
+        let on_off = |value: bool| self.locale.get(if value { "on" } else { "off" });
+        let settings = &self.effects.settings;
+
+        let (key, value) = match setting {
+            Setting::Mode => ("setting.mode", self.locale.get(self.sim.mode.key())),
+            Setting::Language => ("setting.language", self.locale.name()),
+            // Themes without a translated name are shown with the name of their folder.
+            Setting::Theme => (
+                "setting.theme",
+                self.locale
+                    .find(&format!("theme.{}", self.assets.theme))
+                    .unwrap_or(self.assets.theme.as_str())
+                    .to_string(),
+            ),
+            Setting::ScreenShake => ("setting.screen_shake", on_off(settings.screen_shake)),
+            Setting::HitStop => ("setting.hit_stop", on_off(settings.hit_stop)),
+            Setting::Flash => ("setting.flash", on_off(settings.flash)),
+            Setting::Tint => ("setting.tint", on_off(settings.tint)),
+            Setting::Autopilot => ("setting.autopilot", on_off(self.assist)),
+        };
 
-        // Displays the current score count
-        let mut text = graphics::Text::new(format!("{}", self.score));
-        text.set_font(font, graphics::PxScale::from(100.0));
-        graphics::draw(
-            ctx,
-            &text,
-            graphics::DrawParam::default()
-                .dest(Point2 {
-                    x: (SCREEN_WIDTH - text.width(ctx)) / 2.0,
-                    y: (SCREEN_HEIGHT - text.height(ctx)) / 6.0,
-                })
-                .color(graphics::Color::BLACK),
-        )
-        .unwrap();
+        self.locale.format(key, &[("value", &value)])
     }
-}
-impl EventHandler for MainState {
-    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        // Restarts the game if player is dead.
-        if self.play_state == PlayState::Dead {
-            self.restart();
+
+    // Switches to another language and renames the window with its title.
+    fn set_language(&mut self, ctx: &mut Context, language: &str) {
+        match Locale::new(ctx, language) {
+            Ok(locale) => {
+                self.locale = locale;
+                graphics::set_window_title(ctx, &self.locale.get("title"));
+            }
+            Err(error) => eprintln!("Could not load language {}: {}", language, error),
         }
+    }
+
+    // Changes the given setting to its next (or previous) value.
+    fn change_setting(&mut self, ctx: &mut Context, setting: Setting, forward: bool) {
+        match setting {
+            Setting::Mode => {
+                let count = GameMode::ALL.len();
+                let current = GameMode::ALL
+                    .iter()
+                    .position(|mode| *mode == self.sim.mode)
+                    .unwrap_or(0);
+                let next = if forward {
+                    (current + 1) % count
+                } else {
+                    (current + count - 1) % count
+                };
+
+                self.sim.set_mode(GameMode::ALL[next]);
+            }
+            Setting::Language => {
+                let count = self.languages.len();
+                let current = self
+                    .languages
+                    .iter()
+                    .position(|language| *language == self.locale.language)
+                    .unwrap_or(0);
+                let next = if forward {
+                    (current + 1) % count
+                } else {
+                    (current + count - 1) % count
+                };
+
+                let language = self.languages[next].clone();
+                self.set_language(ctx, &language);
+            }
+            Setting::Theme => {
+                let count = self.themes.len();
+                let current = self
+                    .themes
+                    .iter()
+                    .position(|theme| *theme == self.assets.theme)
+                    .unwrap_or(0);
+                let next = if forward {
+                    (current + 1) % count
+                } else {
+                    (current + count - 1) % count
+                };
 
-        // Subtracts length of last frame from countdown fields (only if in Play state).
-        let delta = ggez::timer::delta(ctx).as_nanos() as f32;
-        if self.play_state.is_playing() {
-            self.time_until_next_pipe -= delta;
-            self.time_until_next_enemy -= delta;
-            self.time_until_next_boost -= delta;
+                match self.assets.set_theme(ctx, &self.themes[next]) {
+                    Ok(reloaded) => self.mixer.assets_reloaded(&reloaded),
+                    Err(error) => {
+                        eprintln!("Could not load theme {}: {}", self.themes[next], error)
+                    }
+                }
+            }
+            Setting::ScreenShake => {
+                self.effects.settings.screen_shake = !self.effects.settings.screen_shake
+            }
+            Setting::HitStop => self.effects.settings.hit_stop = !self.effects.settings.hit_stop,
+            Setting::Flash => self.effects.settings.flash = !self.effects.settings.flash,
+            Setting::Tint => self.effects.settings.tint = !self.effects.settings.tint,
+            Setting::Autopilot => self.assist = !self.assist,
         }
 
-        // Removes boost if there is an active one and the countdown is over.
-        if self.has_boost {
-            self.boost_duration -= delta;
+        self.events.push(GameEvent::MenuSelect);
+    }
 
-            if self.boost_duration <= 0.0 {
-                self.has_boost = false;
+    // Presses the menu button under the given window position (if there is one). Returns if a button was pressed.
+    fn click_menu(&mut self, ctx: &mut Context, x: f32, y: f32) -> bool {
+        if self.sim.play_state.is_playing() {
+            return false;
+        }
 
-                self.multiplier = 1.0;
+        match self.menu.button_at(self.viewport.to_game(Point2 { x, y })) {
+            Some(index) => {
+                self.menu.selected = index;
+                self.change_setting(ctx, self.menu.selected_setting(), true);
+                true
             }
+            None => false,
         }
+    }
+
+    // Starts a new online race on the course made from the seed. The other players in it become ghosts.
+    fn start_race(&mut self, seed: u64, players: &[(u32, String)]) {
+        self.restart();
+        self.sim.respawn();
+        self.sim.seed(seed);
+        self.sim.play_state = PlayState::Play;
+        self.race_tick = 0;
+        self.race_results.clear();
+
+        let own_id = self.net.as_ref().and_then(|net| net.id);
+        self.ghosts = players
+            .iter()
+            .filter(|(id, _)| Some(*id) != own_id)
+            .map(|(id, name)| Ghost::new(*id, name))
+            .collect();
+
+        self.events.push(GameEvent::MenuSelect);
+    }
+
+    // Starts recording a single player game on a seeded course and brings the best run on it as a ghost.
+    // Games flown by the autopilot or a bot are not recorded, so they can not become the best run.
+    fn start_recording(&mut self, ctx: &mut Context) {
+        let seed = match self.seed {
+            Some(seed)
+                if self.sim.mode == GameMode::Single
+                    && self.net.is_none()
+                    && !self.assist
+                    && self.agent.is_none() =>
+            {
+                seed
+            }
+            _ => return,
+        };
 
-        // Generates a new pipe and resets the countdown until the next one.
-        if self.play_state.is_playing() && self.time_until_next_pipe <= 0.0 {
-            let random_y = self.rng.gen_range(67.0..481.0);
+        self.recording = Some(Replay::starting_from(&self.sim.players[0].entity));
+        self.best_run = Replay::load(ctx, seed).map(ReplayGhost::new);
+    }
 
-            let pipe = PipeEntity::new(random_y);
-            self.pipes.push_back(pipe);
+    // Saves the recorded run if it is better than the best one so far.
+    fn finish_recording(&mut self, ctx: &mut Context) {
+        let (recording, seed) = match (self.recording.take(), self.seed) {
+            (Some(recording), Some(seed)) => (recording, seed),
+            _ => return,
+        };
 
-            self.time_until_next_pipe = self.rng.gen_range(1.0..4.5) * 1000000000.0;
+        let best = self
+            .best_run
+            .as_ref()
+            .map_or(-1, |ghost| ghost.replay.score());
+        if recording.score() > best {
+            if let Err(error) = recording.save(ctx, seed) {
+                eprintln!("Could not save the replay of seed {}: {}", seed, error);
+            }
         }
+    }
 
-        // Generates a new enemy and resets the countdown until the next one.
-        if self.play_state.is_playing() && self.time_until_next_enemy <= 0.0 {
-            let random_y = self.rng.gen_range(63.0..705.0);
+    // Tells the lobby server this player is gone, so the others do not wait for them.
+    fn leave_race(&mut self) {
+        if let Some(net) = self.net.as_mut() {
+            net.leave();
+        }
+    }
 
-            let enemy = EnemyEntity::new(random_y);
-            self.enemies.push_back(enemy);
+    // Reacts to the messages of the lobby server and the other players.
+    fn receive(&mut self, event: NetEvent) {
+        match event {
+            NetEvent::Started { seed, players } => self.start_race(seed, &players),
+            NetEvent::Input {
+                id,
+                first_tick,
+                y,
+                flaps,
+            } => {
+                if let Some(ghost) = self.ghosts.iter_mut().find(|ghost| ghost.id == id) {
+                    ghost.receive(first_tick, y, &flaps);
+                }
+            }
+            NetEvent::Finished { id, score } => {
+                if let Some(ghost) = self.ghosts.iter_mut().find(|ghost| ghost.id == id) {
+                    ghost.score = Some(score);
+                }
+            }
+            NetEvent::Results { scores } => {
+                self.race_results = scores;
+                self.ghosts.clear();
+            }
+        }
+    }
 
-            self.time_until_next_enemy = self.rng.gen_range(6.0..12.0) * 1000000000.0;
+    // Draws the hitboxes and velocities of all entities and the information panel of the debug overlay.
+    fn draw_debug(&mut self, ctx: &mut Context) -> GameResult {
+        for player in self.sim.players.iter() {
+            let entity = &player.entity;
+            draw_outline(entity.zone, ctx)?;
+            draw_vector(
+                ctx,
+                entity.position,
+                Vector2 {
+                    x: 0.0,
+                    y: entity.physics.velocity,
+                },
+            )?;
         }
 
-        // Create a new boost (if there are no active ones at the moment) and resets the countdown until the next one.
-        if self.play_state.is_playing()
-            && self.time_until_next_boost <= 0.0
-            && self.has_boost == false
-        {
-            // The second random value is used for determining the type of the newly created boost.
-            let random_y = self.rng.gen_range(48.0..720.0);
-            let random_val = self.rng.gen_range(0.0..18.0);
+        for pipe in self.sim.pipes.iter() {
+            draw_outline(pipe.top_zone, ctx)?;
+            draw_outline(pipe.bottom_zone, ctx)?;
+            draw_vector(
+                ctx,
+                pipe.position,
+                Vector2 {
+                    x: -PIPE_SPEED * self.sim.multiplier,
+                    y: 0.0,
+                },
+            )?;
+        }
 
-            let boost = BoostEntity::new(random_y, random_val);
-            self.boosts.push_back(boost);
+        for enemy in self.sim.enemies.iter() {
+            draw_outline(enemy.zone, ctx)?;
+            draw_vector(
+                ctx,
+                enemy.position,
+                Vector2 {
+                    x: -ENEMY_SPEED * self.sim.multiplier,
+                    y: 0.0,
+                },
+            )?;
+        }
 
-            self.time_until_next_boost = self.rng.gen_range(10.0..30.0) * 1000000000.0;
+        for boost in self.sim.boosts.iter() {
+            draw_outline(boost.zone, ctx)?;
+            draw_vector(
+                ctx,
+                boost.position,
+                Vector2 {
+                    x: -BOOST_SPEED,
+                    y: 0.0,
+                },
+            )?;
         }
 
-        // Gets the new state of the player (but stores it in a new variable to compare it with the previous one).
-        let state = self.player.update(ctx, &self.play_state);
+        // The countdowns are stored in ns.
+        let seconds = |ns: f32| ns / 1000000000.0;
+        let boost = if self.sim.has_boost {
+            format!(
+                "{:.1} s left (x{})",
+                seconds(self.sim.boost_duration),
+                self.sim.multiplier
+            )
+        } else {
+            "none".to_string()
+        };
 
-        // Checks if the player touches the ground and has a spare life to use.
-        if self.player.hits_ground() && self.lifes > 1 {
-            self.player.prevent_hitting_ground();
-            self.lifes -= 1;
+        let mut lines = vec![
+            format!(
+                "FPS: {:.0} ({:.1} ms)",
+                ggez::timer::fps(ctx),
+                ggez::timer::average_delta(ctx).as_secs_f64() * 1000.0
+            ),
+            format!(
+                "Time scale: x{}{}",
+                self.debug.scale(),
+                if self.debug.paused { " (paused)" } else { "" }
+            ),
+            format!(
+                "Pipes: {}  Enemies: {}  Boosts: {}  Particles: {}",
+                self.sim.pipes.len(),
+                self.sim.enemies.len(),
+                self.sim.boosts.len(),
+                self.particles.count()
+            ),
+            format!(
+                "Next pipe: {:.1} s  enemy: {:.1} s  boost: {:.1} s",
+                seconds(self.sim.time_until_next_pipe),
+                seconds(self.sim.time_until_next_enemy),
+                seconds(self.sim.time_until_next_boost)
+            ),
+            format!("Boost: {}", boost),
+        ];
+        for player in self.sim.players.iter() {
+            lines.push(format!(
+                "Player {} y: {:.0}  velocity: {:.1}",
+                player.index + 1,
+                player.entity.position.y,
+                player.entity.physics.velocity
+            ));
         }
+        lines.push("F5 pause  F6 step  F7/F8 slower/faster".to_string());
+
+        self.debug.draw_info(ctx, self.assets.font("main"), &lines)
+    }
 
-        // Starts the game if it is not.
-        if self.play_state.is_playing() == false && state.is_playing() {
-            self.play_state = PlayState::Play;
+    // Runs a single step of the game.
+    fn tick(&mut self, ctx: &mut Context) -> GameResult<()> {
+        // Counts down the screen effects. During a hit-stop the whole game is frozen for a moment.
+        let seconds = TICK_SECONDS;
+        self.effects.update(seconds);
+        if self.effects.is_frozen() {
+            return Ok(());
         }
 
-        // Updates pipes and marks these that need to be removed.
-        for pipe in self.pipes.iter_mut() {
-            pipe.update(self.multiplier);
+        // Restarts the game if player is dead. Online races are restarted by the lobby server instead (see start_race).
+        let online = self.net.is_some();
+        if self.sim.play_state == PlayState::Dead && !online {
+            self.restart();
+        }
 
-            let pos = pipe.position;
-            if self.player.zone.overlaps(&pipe.bottom_zone)
-                || self.player.zone.overlaps(&pipe.top_zone)
-            {
-                self.lifes -= 1;
+        // Space, a left click or a touch all count as a flap of the first player, which also starts the game from the start and game over screens.
+        // The other players can only flap while playing, since their keys also move through the menu.
+        // A bot playing over stdin and stdout flaps the first player too, so it can also start the game.
+        let playing = self.sim.play_state.is_playing();
+        let mut held: Vec<bool> = self
+            .sim
+            .players
+            .iter()
+            .map(|player| player.input.is_held(ctx) && (player.index == 0 || playing))
+            .collect();
+        if let Some(agent) = self.agent.as_mut() {
+            held[0] |= agent.poll(&self.sim);
+        }
 
-                if self.lifes > 0 {
-                    pipe.is_passed = true;
+        // The autopilot flies every player on the start screen (the attract mode) and the first one while playing with the assist on.
+        // After a crash in an online race the player stays down, since a flap there would bring them back for a game of their own.
+        let attract = self.sim.play_state == PlayState::StartScreen;
+        let dead = self.sim.play_state == PlayState::Dead;
+        let flaps: Vec<bool> = self
+            .sim
+            .players
+            .iter()
+            .map(|player| {
+                let decide = || self.autopilot.decide(&player.entity, &self.sim);
+                if attract {
+                    held[player.index] || decide()
+                } else if playing && player.index == 0 && self.assist {
+                    decide()
                 } else {
-                    self.hit_pipe = true;
+                    held[player.index] && !(online && dead)
                 }
-            }
+            })
+            .collect();
+        let local_flap = flaps[0];
+
+        let result = self.sim.step(&flaps, &mut self.events);
+        // The flaps of the attract mode are not heard.
+        if attract && !held[0] {
+            self.events.retain(|event| *event != GameEvent::Flap);
+        }
+
+        // Starts the game if it is not. Online races are started by the lobby server instead.
+        // The pipes and enemies of the attract mode are cleared away for the real game.
+        let started_now = !playing && held[0] && self.sim.players[0].entity.has_jumped && !online;
+        if started_now {
+            self.sim.clear_course();
+            self.sim.play_state = PlayState::Play;
+            self.events.push(GameEvent::MenuSelect);
 
-            if pos.x <= -(PIPE_WIDTH / 2.0) {
-                self.score += 1;
-                pipe.is_passed = true;
+            if let Some(seed) = self.seed {
+                self.sim.seed(seed);
             }
         }
 
-        // Updates enemies and marks these that need to be removed.
-        for enemy in self.enemies.iter_mut() {
-            enemy.update(self.multiplier);
+        // Sends the flap of this tick to the other players of the race and replays theirs.
+        if let Some(net) = self.net.as_mut() {
+            if playing {
+                net.send_input(
+                    self.race_tick,
+                    local_flap,
+                    self.sim.players[0].entity.position.y,
+                );
+                self.race_tick += 1;
+            }
+        }
+        for ghost in self.ghosts.iter_mut() {
+            ghost.update();
+        }
 
-            let pos = enemy.position;
+        // Scrolls the background layers.
+        self.parallax.update(self.sim.multiplier);
+        self.parallax.wrap(&self.assets);
 
-            if self.player.zone.overlaps(&enemy.zone) {
-                self.lifes -= 1;
+        // Shows the boosts taken with a burst in their color.
+        for (effect, position) in result.collected.iter() {
+            self.particles
+                .emit(&Emitter::burst(effect.color()), *position);
+        }
 
-                if self.lifes > 0 {
-                    enemy.is_passed = true;
-                } else {
-                    self.hit_enemy = true;
-                }
+        // Lets the particles and effects react to the players that got hurt or crashed.
+        for player in self.sim.players.iter() {
+            let position = player.entity.position;
+
+            if player.crashed {
+                self.particles.emit(&Emitter::explosion(), position);
+                self.effects.shake(DEATH_SHAKE);
+                self.effects.flash(FLASH_TIME);
+            } else if player.hurt {
+                self.particles.emit(&Emitter::feathers(), position);
+                self.effects.shake(HIT_SHAKE);
+                self.effects.hit_stop(HIT_STOP_TIME);
             }
+        }
 
-            if pos.x <= -(ENEMY_WIDTH / 2.0) {
-                enemy.is_passed = true;
+        // Online the score is reported as soon as the game is over.
+        if result.over {
+            if let Some(net) = self.net.as_mut() {
+                net.finish(self.sim.teams[0].score);
             }
         }
 
-        // Updates boosts and marks these that need to be removed.
-        for boost in self.boosts.iter_mut() {
-            boost.update();
+        // Records the flap of this tick and replays the same tick of the best run.
+        if started_now {
+            self.start_recording(ctx);
+        } else if let Some(recording) = self.recording.as_mut() {
+            for (_, y) in result.placed.iter() {
+                recording.record_place(*y);
+            }
+            for _ in self
+                .events
+                .iter()
+                .filter(|event| **event == GameEvent::PipePassed { player: 0 })
+            {
+                recording.record_point();
+            }
+            recording.record(local_flap);
 
-            let pos = boost.position;
+            if let Some(ghost) = self.best_run.as_mut() {
+                ghost.update();
+            }
+        }
+        if result.over {
+            self.finish_recording(ctx);
+        }
 
-            if self.player.zone.overlaps(&boost.zone) {
-                boost.is_collected = true;
-                self.assets.boost_sound.play_detached(ctx).unwrap();
+        // Tints the screen with the color of the active boost.
+        self.effects.tint = self.sim.active_boost().map(|boost| boost.color());
 
-                match boost.effect {
-                    BoostType::BonusLife => {
-                        self.lifes += 1;
-                    }
-                    BoostType::SlowDown => {
-                        self.has_boost = true;
-                        self.boost_duration = BOOST_DURATION;
-                        self.multiplier = 0.5;
-                    }
-                    BoostType::SpeedUp => {
-                        self.has_boost = true;
-                        self.boost_duration = BOOST_DURATION;
-                        self.multiplier = 1.5;
-                    }
+        // Makes the score pop when a point is scored.
+        if self
+            .events
+            .iter()
+            .any(|event| matches!(event, GameEvent::PipePassed { .. }))
+        {
+            self.hud.pop_score();
+        }
+        self.hud.update(seconds);
+
+        // Leaves a dust trail behind the players while the speed-up boost is active.
+        if self.sim.has_boost && self.sim.multiplier > 1.0 {
+            for player in self.sim.players.iter().filter(|player| player.alive) {
+                let behind = Point2 {
+                    x: player.entity.position.x - (FERRIS_WIDTH / 2.0),
+                    y: player.entity.position.y,
                 };
+                self.particles.emit(&Emitter::dust(), behind);
             }
+        }
 
-            if pos.x <= -(BOOST_WIDTH / 2.0) {
-                boost.is_passed = true;
-            }
+        self.particles.update(seconds);
+
+        for player in self.sim.players.iter_mut() {
+            player.entity.animate(&self.assets, seconds);
+        }
+        for ghost in self.ghosts.iter_mut() {
+            ghost.entity.animate(&self.assets, seconds);
+        }
+        if let Some(ghost) = self.best_run.as_mut() {
+            ghost.entity.animate(&self.assets, seconds);
+        }
+        for enemy in self.sim.enemies.iter_mut() {
+            enemy.animate(seconds);
+        }
+        for boost in self.sim.boosts.iter_mut() {
+            boost.animate(seconds);
         }
 
-        // Checks if the game is over.
-        if self.is_over() {
-            self.assets.death_sound.play_detached(ctx).unwrap();
-            self.play_state.set_dead();
+        // Plays the sounds of everything that happened during this frame.
+        for event in self.events.drain(..) {
+            self.mixer.play_event(ctx, &mut self.assets, event)?;
         }
 
-        // Removes all pipes that are already passed.
-        self.pipes.retain(|pipe| pipe.is_passed == false);
+        Ok(())
+    }
+}
+impl EventHandler for MainState {
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let seconds = ggez::timer::delta(ctx).as_secs_f32();
+        self.reload_resources(ctx, seconds);
+
+        let net_events = match self.net.as_mut() {
+            Some(net) => net.update(seconds),
+            None => Vec::new(),
+        };
+        for event in net_events {
+            self.receive(event);
+        }
 
-        // Removes all enemies that are already passed.
-        self.enemies.retain(|enemy| enemy.is_passed == false);
+        // There can be any number of ticks in a frame, depending on how long it took.
+        // The debug overlay can also pause the game or make it slower or faster, so there can be any number of updates in a tick.
+        while ggez::timer::check_update_time(ctx, TICKS_PER_SECOND) {
+            for _ in 0..self.debug.updates_this_tick() {
+                self.tick(ctx)?;
+            }
+        }
 
-        // Removes all boosts that are already passed or collected.
-        self.boosts
-            .retain(|boost| boost.is_passed == false && boost.is_collected == false);
+        // Plays the menu music on the start and game over screens and the gameplay one while playing.
+        let track = if self.sim.play_state.is_playing() {
+            MusicTrack::Gameplay
+        } else {
+            MusicTrack::Menu
+        };
+        self.mixer.play_music(track);
+        self.mixer.update(ctx, &mut self.assets, seconds)?;
 
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        // Sets the background color to light blue before displaying the background image.
-        let light_blue = graphics::Color::from_rgb(77, 193, 203);
-        graphics::clear(ctx, light_blue);
-
-        // Displays the background image.
-        graphics::draw(
-            ctx,
-            &self.assets.background_image,
-            graphics::DrawParam::default(),
-        )?;
-
-        // Draw parameters for the two different state logos. (StartScreen and Dead)
-        let offset = Point2 { x: 0.5, y: 0.5 };
-        let pos = Point2 {
-            x: SCREEN_WIDTH / 2.0,
-            y: SCREEN_HEIGHT / 4.0,
-        };
+        // Sets the background color (light blue in the classic theme) before displaying the background image.
+        graphics::clear(ctx, self.assets.color("clear"));
 
-        // Displays the logo during the StartScreen state.
-        if self.play_state == PlayState::StartScreen {
-            graphics::draw(
-                ctx,
-                &self.assets.logo_start_screen_image,
-                graphics::DrawParam::default().dest(pos).offset(offset),
-            )?;
+        // Moves the camera if the screen is shaking.
+        self.viewport
+            .apply_offset(ctx, self.effects.camera_offset())?;
+
+        // Displays the background layers.
+        self.parallax.draw(ctx, &self.assets, false)?;
+
+        // Displays the title during the StartScreen state.
+        let online = self.net.is_some();
+        if self.sim.play_state == PlayState::StartScreen {
+            self.hud
+                .draw_title(ctx, &self.assets, &self.locale, false, online)?;
         }
 
-        // Displays 'Game Over' message and the best score during the Dead state.
-        if self.play_state == PlayState::Dead {
-            graphics::draw(
-                ctx,
-                &self.assets.logo_game_over_image,
-                graphics::DrawParam::default().dest(pos).offset(offset),
-            )?;
+        // Displays 'Game Over' message and the best score (or the results of the last race) during the Dead state.
+        if self.sim.play_state == PlayState::Dead {
+            self.hud
+                .draw_title(ctx, &self.assets, &self.locale, true, online)?;
+            if self.race_results.is_empty() {
+                self.hud
+                    .draw_best_score(ctx, &self.assets, &self.locale, self.best_score)?;
+            } else {
+                self.hud
+                    .draw_results(ctx, &self.assets, &self.locale, &self.race_results)?;
+            }
 
-            let font = graphics::Font::new(ctx, "/FlappyBird.ttf")?;
-            let mut text = graphics::Text::new(format!("Best score: {}", self.best_score));
-            text.set_font(font, graphics::PxScale::from(50.0));
+            if self.sim.mode == GameMode::Versus {
+                self.hud
+                    .draw_winner(ctx, &self.assets, &self.locale, self.sim.winner)?;
+            }
+        }
 
-            let text_pos = Point2 {
-                x: (SCREEN_WIDTH - text.width(ctx)) / 2.0,
-                y: (SCREEN_HEIGHT - text.height(ctx)) / 2.0,
-            };
+        // Draws the other players of an online race (until they crash) behind the local one.
+        for ghost in self.ghosts.iter_mut().filter(|ghost| !ghost.is_finished()) {
+            ghost.entity.draw(ctx, &self.assets)?;
+        }
 
-            graphics::draw(
-                ctx,
-                &text,
-                graphics::DrawParam::default()
-                    .dest(text_pos)
-                    .color(graphics::Color::BLACK),
-            )?;
+        // Draws the best run on a seeded course (until it crashes).
+        if let Some(ghost) = self.best_run.as_mut().filter(|ghost| !ghost.is_finished()) {
+            ghost.entity.draw(ctx, &self.assets)?;
         }
 
-        // Draws the player.
-        self.player.draw(ctx, &self.assets)?;
+        // Draws the players.
+        for player in self.sim.players.iter_mut() {
+            player.entity.draw(ctx, &self.assets)?;
+        }
 
         // Draws the pipes.
-        for pipe in self.pipes.iter_mut() {
+        for pipe in self.sim.pipes.iter_mut() {
             pipe.draw(ctx, &self.assets)?;
         }
 
         // Draws the enemies.
-        for enemy in self.enemies.iter_mut() {
+        for enemy in self.sim.enemies.iter_mut() {
             enemy.draw(ctx, &self.assets)?;
         }
 
         // Draws the boosts.
-        for boost in self.boosts.iter_mut() {
+        for boost in self.sim.boosts.iter_mut() {
             boost.draw(ctx, &self.assets)?;
         }
 
-        // Drawss the scores.
-        if self.play_state.is_playing() {
-            self.draw_stats(ctx);
+        // Draws the particles.
+        self.particles.draw(ctx)?;
+
+        // Draws the ground over the pipes.
+        self.parallax.draw(ctx, &self.assets, true)?;
+
+        // Draws the scores.
+        if self.sim.play_state.is_playing() {
+            let stats = Stats {
+                teams: self
+                    .sim
+                    .teams
+                    .iter()
+                    .map(|team| TeamStats {
+                        lifes: team.lifes,
+                        score: team.score,
+                    })
+                    .collect(),
+                best_score: self.best_score,
+                multiplier: self.sim.multiplier,
+                boost: self
+                    .sim
+                    .active_boost()
+                    .map(|boost| (boost, self.sim.boost_duration / BOOST_DURATION)),
+                best_run: self
+                    .best_run
+                    .as_ref()
+                    .map(|ghost| self.sim.teams[0].score - ghost.score()),
+            };
+            self.hud
+                .draw_stats(ctx, &self.assets, &self.locale, &stats)?;
+        }
+
+        // Draws the settings menu on the start and game over screens.
+        if !self.sim.play_state.is_playing() {
+            let labels: Vec<String> = self
+                .menu
+                .settings
+                .iter()
+                .map(|setting| self.setting_label(*setting))
+                .collect();
+
+            let font = self.assets.font(self.locale.font());
+            self.menu.draw(ctx, font, &labels)?;
+        }
+
+        // Draws the flash and tint effects, the debug overlay and puts the camera back in place.
+        self.effects.draw_overlay(ctx)?;
+        if self.debug.enabled {
+            self.draw_debug(ctx)?;
         }
+        self.viewport
+            .apply_offset(ctx, Vector2 { x: 0.0, y: 0.0 })?;
+
+        // Covers the parts of the window outside of the game area.
+        self.viewport.draw_bars(ctx)?;
 
         graphics::present(ctx)?;
         std::thread::yield_now();
 
         Ok(())
     }
+
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        self.viewport.resize(ctx, width, height).unwrap();
+    }
+
+    fn key_down_event(
+        &mut self,
+        ctx: &mut Context,
+        keycode: KeyCode,
+        _keymods: KeyMods,
+        repeat: bool,
+    ) {
+        if repeat {
+            return;
+        }
+
+        // Navigating the settings menu.
+        if !self.sim.play_state.is_playing() {
+            match keycode {
+                KeyCode::Up => {
+                    self.menu.select_previous();
+                    self.events.push(GameEvent::MenuMove);
+                }
+                KeyCode::Down => {
+                    self.menu.select_next();
+                    self.events.push(GameEvent::MenuMove);
+                }
+                KeyCode::Left => self.change_setting(ctx, self.menu.selected_setting(), false),
+                KeyCode::Right | KeyCode::Return => {
+                    self.change_setting(ctx, self.menu.selected_setting(), true)
+                }
+                _ => (),
+            }
+        }
+
+        // Debug keys, the ones besides F3 work only while the overlay is shown.
+        match keycode {
+            KeyCode::F3 => self.debug.toggle(),
+            KeyCode::F5 if self.debug.enabled => self.debug.toggle_pause(),
+            KeyCode::F6 if self.debug.enabled => self.debug.step(),
+            KeyCode::F7 if self.debug.enabled => self.debug.slower(),
+            KeyCode::F8 if self.debug.enabled => self.debug.faster(),
+            _ => (),
+        }
+
+        match keycode {
+            KeyCode::F11 => self.viewport.toggle_fullscreen(ctx).unwrap(),
+            KeyCode::F10 => self.viewport.toggle_mode(ctx).unwrap(),
+            // Muting and changing the master, music and SFX volumes.
+            KeyCode::M => self.mixer.toggle_mute(),
+            KeyCode::Minus => self.mixer.change_volume(Channel::Master, -VOLUME_STEP),
+            KeyCode::Equals => self.mixer.change_volume(Channel::Master, VOLUME_STEP),
+            KeyCode::LBracket => self.mixer.change_volume(Channel::Music, -VOLUME_STEP),
+            KeyCode::RBracket => self.mixer.change_volume(Channel::Music, VOLUME_STEP),
+            KeyCode::Semicolon => self.mixer.change_volume(Channel::Sfx, -VOLUME_STEP),
+            KeyCode::Apostrophe => self.mixer.change_volume(Channel::Sfx, VOLUME_STEP),
+            // Keeps the default ggez behaviour of quitting on Escape.
+            KeyCode::Escape => {
+                self.leave_race();
+                event::quit(ctx)
+            }
+            _ => (),
+        }
+    }
+
+    // A click on a menu button should not make Ferris flap (and start the game).
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if button == MouseButton::Left && self.click_menu(ctx, x, y) {
+            self.sim.players[0].input.mouse_blocked = true;
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, _ctx: &mut Context, button: MouseButton, _x: f32, _y: f32) {
+        if button == MouseButton::Left {
+            self.sim.players[0].input.mouse_blocked = false;
+        }
+    }
+
+    fn quit_event(&mut self, _ctx: &mut Context) -> bool {
+        self.leave_race();
+        false
+    }
+
+    fn touch_event(&mut self, ctx: &mut Context, phase: TouchPhase, x: f64, y: f64) {
+        if phase == TouchPhase::Started && self.click_menu(ctx, x as f32, y as f32) {
+            return;
+        }
+
+        self.sim.players[0].input.touch(phase);
+    }
 }
 
 fn main() {
     // Path to resources
     let path = path::PathBuf::from("./resources");
 
+    // In the development mode the resources are loaded again when they change on disk.
+    let watcher = if std::env::args().any(|arg| arg == "--dev") {
+        Some(ResourceWatcher::new(path.clone()))
+    } else {
+        None
+    };
+
+    // Online races: --connect <server address> [--name <player name>], the port can be left out.
+    let net = arg_value("--connect").map(|server| {
+        let server = if server.contains(':') {
+            server
+        } else {
+            format!("{}:{}", server, DEFAULT_PORT)
+        };
+        let name = arg_value("--name").unwrap_or_else(|| "ferris".to_string());
+
+        NetClient::connect(&server, &name).unwrap()
+    });
+
+    // A course that stays the same in every game: --seed <number>, a random course when it is not a number.
+    let seed = arg_value("--seed").and_then(|seed| seed.parse().ok());
+    let mut agent_io = None;
+
+    // The neural network showcase: --population [size], on the course of --seed (or a random one).
+    // Smaller populations than the ones kept for the next generation are made that big.
+    let population = std::env::args().any(|arg| arg == "--population").then(|| {
+        arg_value("--population")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_POPULATION)
+            .max(ELITE_COUNT)
+    });
+
+    // A bot in another program playing over stdin and stdout: --agent-stdio [--headless] [--agent-timeout <ms>] [--agent-default flap|wait].
+    // Headless it plays games one after another without a window until the bot closes its output, waiting up to the timeout
+    // for every action. In the window the game does not wait for the bot (see AgentIo::poll).
+    if std::env::args().any(|arg| arg == "--agent-stdio") {
+        let timeout = arg_value("--agent-timeout")
+            .and_then(|ms| ms.parse().ok())
+            .unwrap_or(DEFAULT_TIMEOUT_MS);
+        let default_flap =
+            arg_value("--agent-default").map_or(DEFAULT_FLAP, |action| action == "flap");
+        let mut agent = AgentIo::new(Duration::from_millis(timeout), default_flap);
+
+        if std::env::args().any(|arg| arg == "--headless") {
+            run_headless(&mut agent, seed);
+            return;
+        }
+        agent_io = Some(agent);
+    }
+
     // Setting the window size
-    // I saw that it is possible to make the game resizeable but at this point I decided to keep the size strictly defined.
-    let win_mode = WindowMode::default().dimensions(SCREEN_WIDTH, SCREEN_HEIGHT);
+    // The window is resizable, everything is still positioned in SCREEN_WIDTH x SCREEN_HEIGHT game coordinates and the Viewport fits them in the window.
+    // F11 toggles fullscreen and F10 switches between letterboxing and stretching.
+    let win_mode = WindowMode::default()
+        .dimensions(SCREEN_WIDTH, SCREEN_HEIGHT)
+        .min_dimensions(SCREEN_WIDTH / 4.0, SCREEN_HEIGHT / 4.0)
+        .resizable(true);
 
     // Customizing the window
     let win_setup = WindowSetup::default()
-        .title("Flappy Ferrris")
+        .title("Flappy Ferris")
         .icon("/icon.png");
 
     // Generating the configuration
@@ -439,7 +1026,21 @@ fn main() {
         .build()
         .unwrap();
 
+    if let Some(size) = population {
+        let seed = seed.unwrap_or_else(rand::random);
+        let state = EvolutionState::new(&mut ctx, size, seed);
+        event::run(ctx, event_loop, state);
+    }
+
     // Running the game
-    let state = MainState::new(&mut ctx);
+    let state = MainState::new(&mut ctx, watcher, net, seed, agent_io);
     event::run(ctx, event_loop, state);
 }
+
+// The value given after a command line option, e.g. the address after --connect.
+fn arg_value(option: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == option)?;
+
+    args.get(index + 1).cloned()
+}
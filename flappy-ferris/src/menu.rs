@@ -0,0 +1,106 @@
+use crate::entities::SCREEN_WIDTH;
+use crate::hud::{Label, TextStyle};
+use ggez::graphics::{self, Rect};
+use ggez::mint::Point2;
+use ggez::{Context, GameResult};
+
+// Used for positioning the menu buttons.
+pub const MENU_TOP: f32 = 400.0;
+pub const BUTTON_WIDTH: f32 = 520.0;
+pub const BUTTON_HEIGHT: f32 = 36.0;
+pub const BUTTON_SPACING: f32 = 4.0;
+
+// The settings that can be changed from the menu on the start and game over screens.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Setting {
+    Mode,
+    Language,
+    Theme,
+    ScreenShake,
+    HitStop,
+    Flash,
+    Tint,
+    Autopilot,
+}
+
+// A vertical list of buttons, one for each setting.
+// Up and Down select a button, Left, Right and Enter change its setting and clicking a button changes it too.
+pub struct Menu {
+    pub settings: Vec<Setting>,
+    pub selected: usize,
+    // The texts of the buttons, laid out again only when a setting changes.
+    labels: Vec<Label>,
+}
+impl Menu {
+    pub fn new(settings: Vec<Setting>) -> Self {
+        let labels = settings
+            .iter()
+            .map(|_| Label::new(TextStyle::new(30.0, graphics::Color::BLACK)))
+            .collect();
+
+        Self {
+            settings,
+            selected: 0,
+            labels,
+        }
+    }
+
+    pub fn selected_setting(&self) -> Setting {
+        self.settings[self.selected]
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.settings.len();
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = (self.selected + self.settings.len() - 1) % self.settings.len();
+    }
+
+    // The area of the button with the given index (in game coordinates).
+    pub fn button(&self, index: usize) -> Rect {
+        Rect {
+            x: (SCREEN_WIDTH - BUTTON_WIDTH) / 2.0,
+            y: MENU_TOP + (index as f32) * (BUTTON_HEIGHT + BUTTON_SPACING),
+            w: BUTTON_WIDTH,
+            h: BUTTON_HEIGHT,
+        }
+    }
+
+    // Finds the button under the given point (in game coordinates).
+    pub fn button_at(&self, point: Point2<f32>) -> Option<usize> {
+        (0..self.settings.len()).find(|&index| self.button(index).contains(point))
+    }
+
+    // Draws the buttons with the given labels (one for each setting, in the same order).
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        font: graphics::Font,
+        labels: &[String],
+    ) -> GameResult {
+        for (index, value) in labels.iter().enumerate() {
+            let button = self.button(index);
+
+            let background = if index == self.selected {
+                graphics::Color::from_rgba(255, 255, 255, 200)
+            } else {
+                graphics::Color::from_rgba(255, 255, 255, 90)
+            };
+            let rectangle =
+                graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), button, background)?;
+            graphics::draw(ctx, &rectangle, graphics::DrawParam::default())?;
+
+            let label = &mut self.labels[index];
+            label.set(font, value);
+
+            let position = Point2 {
+                x: button.x + (button.w - label.width(ctx)) / 2.0,
+                y: button.y + (button.h - label.height(ctx)) / 2.0,
+            };
+            label.draw(ctx, position)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,514 @@
+use crate::entities::{PlayState, PlayerEntity};
+use ggez::graphics::Color;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+// Used for connecting to the lobby server (started with `cargo run --bin lobby`).
+pub const DEFAULT_PORT: u16 = 7878;
+pub const MAX_MESSAGE_SIZE: usize = 1024;
+// Every input message repeats the flaps of this many last ticks, so a few lost packets do not break the ghosts.
+pub const INPUT_WINDOW: usize = 30;
+// How often (in seconds) a client repeats a message until the server answers it.
+pub const RESEND_INTERVAL: f32 = 0.5;
+// Clients that send nothing for this long (in seconds) are dropped from the lobby and their race.
+pub const CLIENT_TIMEOUT: f32 = 10.0;
+// The other players of a race are drawn this transparent.
+pub const GHOST_ALPHA: f32 = 0.4;
+
+// The messages of the network protocol, sent as single lines of text over UDP:
+//   JOIN <name>                          client -> server, also sent again to stay in the lobby and be ready for the next race
+//   WELCOME <id>                         server -> client
+//   START <seed> <id>:<name> ...         server -> clients, everyone plays the course made from the seed
+//   INPUT <id> <first tick> <y> <flaps>  client -> server -> other clients, the flaps are 0s and 1s starting at the first tick
+//   RESULT <id> <score>                  client -> server -> other clients, sent after crashing
+//   RESULTS <name>:<score> ...           server -> clients, sorted from the best score
+//   LEAVE <id>                           client -> server
+// Names can not contain spaces or colons, the client replaces them with '_'.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Message {
+    Join {
+        name: String,
+    },
+    Welcome {
+        id: u32,
+    },
+    Start {
+        seed: u64,
+        players: Vec<(u32, String)>,
+    },
+    Input {
+        id: u32,
+        first_tick: u64,
+        y: f32,
+        flaps: Vec<bool>,
+    },
+    Result {
+        id: u32,
+        score: i128,
+    },
+    Results {
+        scores: Vec<(String, i128)>,
+    },
+    Leave {
+        id: u32,
+    },
+}
+impl Message {
+    pub fn encode(&self) -> String {
+        match self {
+            Message::Join { name } => format!("JOIN {}", name),
+            Message::Welcome { id } => format!("WELCOME {}", id),
+            Message::Start { seed, players } => {
+                let players: Vec<String> = players
+                    .iter()
+                    .map(|(id, name)| format!("{}:{}", id, name))
+                    .collect();
+                format!("START {} {}", seed, players.join(" "))
+            }
+            Message::Input {
+                id,
+                first_tick,
+                y,
+                flaps,
+            } => {
+                let flaps: String = flaps
+                    .iter()
+                    .map(|&flap| if flap { '1' } else { '0' })
+                    .collect();
+                // The height is sent in full, so the ghost ends up exactly where the player was.
+                format!("INPUT {} {} {} {}", id, first_tick, y, flaps)
+            }
+            Message::Result { id, score } => format!("RESULT {} {}", id, score),
+            Message::Results { scores } => {
+                let scores: Vec<String> = scores
+                    .iter()
+                    .map(|(name, score)| format!("{}:{}", name, score))
+                    .collect();
+                format!("RESULTS {}", scores.join(" "))
+            }
+            Message::Leave { id } => format!("LEAVE {}", id),
+        }
+    }
+
+    // Returns None for anything that is not a valid message, so a broken packet is simply skipped.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let message = match words.next()? {
+            "JOIN" => Message::Join {
+                name: words.next()?.to_string(),
+            },
+            "WELCOME" => Message::Welcome {
+                id: words.next()?.parse().ok()?,
+            },
+            "START" => Message::Start {
+                seed: words.next()?.parse().ok()?,
+                players: words
+                    .by_ref()
+                    .map(|player| {
+                        let (id, name) = player.split_once(':')?;
+                        Some((id.parse().ok()?, name.to_string()))
+                    })
+                    .collect::<Option<_>>()?,
+            },
+            "INPUT" => Message::Input {
+                id: words.next()?.parse().ok()?,
+                first_tick: words.next()?.parse().ok()?,
+                y: words.next()?.parse().ok()?,
+                flaps: words
+                    .next()?
+                    .chars()
+                    .map(|flap| match flap {
+                        '0' => Some(false),
+                        '1' => Some(true),
+                        _ => None,
+                    })
+                    .collect::<Option<_>>()?,
+            },
+            "RESULT" => Message::Result {
+                id: words.next()?.parse().ok()?,
+                score: words.next()?.parse().ok()?,
+            },
+            "RESULTS" => Message::Results {
+                scores: words
+                    .by_ref()
+                    .map(|score| {
+                        let (name, score) = score.split_once(':')?;
+                        Some((name.to_string(), score.parse().ok()?))
+                    })
+                    .collect::<Option<_>>()?,
+            },
+            "LEAVE" => Message::Leave {
+                id: words.next()?.parse().ok()?,
+            },
+            _ => return None,
+        };
+
+        // Leftover words mean the message is not what it seems to be.
+        match words.next() {
+            Some(_) => None,
+            None => Some(message),
+        }
+    }
+}
+
+// Makes a name safe to send in a message.
+pub fn clean_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() || c == ':' {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    if name.is_empty() {
+        "ferris".to_string()
+    } else {
+        name
+    }
+}
+
+// Where the client is in the lobby.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NetState {
+    // Waiting for the server to answer the first JOIN.
+    Joining,
+    // In the lobby, ready for the next race.
+    Waiting,
+    Racing,
+    // Crashed, waiting for the others to finish the race.
+    Finished,
+}
+
+// What the game has to react to after the messages of a frame are read.
+#[derive(Debug, PartialEq, Clone)]
+pub enum NetEvent {
+    Started {
+        seed: u64,
+        players: Vec<(u32, String)>,
+    },
+    Input {
+        id: u32,
+        first_tick: u64,
+        y: f32,
+        flaps: Vec<bool>,
+    },
+    Finished {
+        id: u32,
+        score: i128,
+    },
+    Results {
+        scores: Vec<(String, i128)>,
+    },
+}
+
+// The connection of a game to the lobby server.
+pub struct NetClient {
+    socket: UdpSocket,
+    server: SocketAddr,
+    pub name: String,
+    pub id: Option<u32>,
+    pub state: NetState,
+    // The flaps of the local player in every tick of the current race.
+    flaps: Vec<bool>,
+    score: i128,
+    seed: Option<u64>,
+    // Time (in seconds) until the last unanswered message is sent again.
+    resend: f32,
+}
+impl NetClient {
+    pub fn connect(server: &str, name: &str) -> io::Result<Self> {
+        let server = server.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Unknown server {}", server),
+            )
+        })?;
+
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            server,
+            name: clean_name(name),
+            id: None,
+            state: NetState::Joining,
+            flaps: Vec::new(),
+            score: 0,
+            seed: None,
+            resend: 0.0,
+        })
+    }
+
+    // Reads everything the server sent since the last frame and repeats the messages it did not answer yet.
+    pub fn update(&mut self, delta: f32) -> Vec<NetEvent> {
+        let mut events = Vec::new();
+        let mut buffer = [0; MAX_MESSAGE_SIZE];
+
+        loop {
+            let (size, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                // A closed server port shows up as an error on some systems, the client keeps trying.
+                Err(_) => break,
+            };
+            if from != self.server {
+                continue;
+            }
+
+            let message = match std::str::from_utf8(&buffer[..size])
+                .ok()
+                .and_then(Message::parse)
+            {
+                Some(message) => message,
+                None => continue,
+            };
+
+            if let Some(event) = self.receive(message) {
+                events.push(event);
+            }
+        }
+
+        self.resend -= delta;
+        if self.resend <= 0.0 {
+            self.resend = RESEND_INTERVAL;
+
+            match (self.state, self.id) {
+                (NetState::Joining, _) | (NetState::Waiting, _) => self.send(&Message::Join {
+                    name: self.name.clone(),
+                }),
+                (NetState::Finished, Some(id)) => self.send(&Message::Result {
+                    id,
+                    score: self.score,
+                }),
+                _ => (),
+            }
+        }
+
+        events
+    }
+
+    fn receive(&mut self, message: Message) -> Option<NetEvent> {
+        match message {
+            Message::Welcome { id } => {
+                self.id = Some(id);
+                if self.state == NetState::Joining {
+                    self.state = NetState::Waiting;
+                }
+                None
+            }
+            // The server repeats the start until it hears from every player, so only the first one counts.
+            Message::Start { seed, players } if self.seed != Some(seed) => {
+                self.seed = Some(seed);
+                self.state = NetState::Racing;
+                self.flaps.clear();
+                Some(NetEvent::Started { seed, players })
+            }
+            Message::Input {
+                id,
+                first_tick,
+                y,
+                flaps,
+            } => Some(NetEvent::Input {
+                id,
+                first_tick,
+                y,
+                flaps,
+            }),
+            Message::Result { id, score } => Some(NetEvent::Finished { id, score }),
+            Message::Results { scores } if self.state != NetState::Racing => {
+                self.state = NetState::Waiting;
+                Some(NetEvent::Results { scores })
+            }
+            _ => None,
+        }
+    }
+
+    // Sends the flap of the local player in the given tick (together with the ones before it) and where they are.
+    pub fn send_input(&mut self, tick: u64, flap: bool, y: f32) {
+        let id = match (self.state, self.id) {
+            (NetState::Racing, Some(id)) => id,
+            _ => return,
+        };
+
+        self.flaps.truncate(tick as usize);
+        self.flaps.resize(tick as usize, false);
+        self.flaps.push(flap);
+
+        let first = self.flaps.len().saturating_sub(INPUT_WINDOW);
+        self.send(&Message::Input {
+            id,
+            first_tick: first as u64,
+            y,
+            flaps: self.flaps[first..].to_vec(),
+        });
+    }
+
+    // Reports the score of the local player after crashing. It is repeated until the results of the race arrive.
+    pub fn finish(&mut self, score: i128) {
+        if self.state != NetState::Racing {
+            return;
+        }
+
+        self.state = NetState::Finished;
+        self.score = score;
+        self.resend = 0.0;
+    }
+
+    pub fn leave(&mut self) {
+        if let Some(id) = self.id {
+            self.send(&Message::Leave { id });
+        }
+    }
+
+    fn send(&self, message: &Message) {
+        // Lost packets are expected with UDP, so errors are not a reason to stop the game.
+        let _ = self
+            .socket
+            .send_to(message.encode().as_bytes(), self.server);
+    }
+}
+
+// Another player of an online race. The course is the same for everyone, so replaying their flaps
+// moves the ghost the same way they moved. Their reported height fixes it if something was missed (like a lost life).
+pub struct Ghost {
+    pub id: u32,
+    pub name: String,
+    pub entity: PlayerEntity,
+    // The flaps of every tick received so far (None for the ones still missing).
+    flaps: Vec<Option<bool>>,
+    // The next tick to replay.
+    tick: u64,
+    // The last reported height and the tick it was reported in.
+    reported: Option<(u64, f32)>,
+    pub score: Option<i128>,
+}
+impl Ghost {
+    pub fn new(id: u32, name: &str) -> Self {
+        let mut entity = PlayerEntity::new();
+        entity.tint = Color::new(1.0, 1.0, 1.0, GHOST_ALPHA);
+
+        Self {
+            id,
+            name: name.to_string(),
+            entity,
+            flaps: Vec::new(),
+            tick: 0,
+            reported: None,
+            score: None,
+        }
+    }
+
+    pub fn receive(&mut self, first_tick: u64, y: f32, flaps: &[bool]) {
+        let end = first_tick as usize + flaps.len();
+        if self.flaps.len() < end {
+            self.flaps.resize(end, None);
+        }
+        for (i, &flap) in flaps.iter().enumerate() {
+            self.flaps[first_tick as usize + i] = Some(flap);
+        }
+
+        let last = end.saturating_sub(1) as u64;
+        if self.reported.map_or(true, |(tick, _)| tick < last) {
+            self.reported = Some((last, y));
+        }
+    }
+
+    // Replays all ticks that arrived, so a ghost behind because of a slow connection catches up.
+    pub fn update(&mut self) {
+        while let Some(Some(flap)) = self.flaps.get(self.tick as usize) {
+            self.entity.update(*flap, &PlayState::Play);
+
+            if let Some((tick, y)) = self.reported {
+                if tick == self.tick {
+                    self.entity.place_at(y);
+                }
+            }
+            self.tick += 1;
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.score.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: Message) {
+        let line = message.encode();
+        assert_eq!(Message::parse(&line), Some(message), "{}", line);
+    }
+
+    #[test]
+    fn every_message_survives_encoding() {
+        round_trip(Message::Join {
+            name: "ferris".to_string(),
+        });
+        round_trip(Message::Welcome { id: 7 });
+        round_trip(Message::Start {
+            seed: u64::MAX,
+            players: vec![(1, "ann".to_string()), (2, "bob".to_string())],
+        });
+        round_trip(Message::Start {
+            seed: 0,
+            players: Vec::new(),
+        });
+        round_trip(Message::Input {
+            id: 3,
+            first_tick: 120,
+            y: 341.283_45,
+            flaps: vec![true, false, false, true],
+        });
+        round_trip(Message::Result { id: 3, score: -1 });
+        round_trip(Message::Results {
+            scores: vec![("bob".to_string(), 12), ("ann".to_string(), 4)],
+        });
+        round_trip(Message::Leave { id: 3 });
+    }
+
+    #[test]
+    fn cleaned_names_survive_encoding() {
+        for name in ["Ann Lee", "a:b", "tab\tand\nnewline", "Ферис", "  ", ""] {
+            let name = clean_name(name);
+            assert!(!name.is_empty());
+
+            round_trip(Message::Join { name: name.clone() });
+            round_trip(Message::Start {
+                seed: 42,
+                players: vec![(1, name.clone())],
+            });
+            round_trip(Message::Results {
+                scores: vec![(name, 3)],
+            });
+        }
+
+        assert_eq!(clean_name("Ann Lee"), "Ann_Lee");
+        assert_eq!(clean_name("a:b"), "a_b");
+        assert_eq!(clean_name(""), "ferris");
+    }
+
+    #[test]
+    fn broken_messages_are_skipped() {
+        for line in [
+            "",
+            "HELLO",
+            "WELCOME",
+            "WELCOME x",
+            "WELCOME 1 2",
+            "START 1 2-bob",
+            "INPUT 1 0 3.5 0120",
+            "RESULT 1",
+            "RESULTS bob",
+            "LEAVE -1",
+        ] {
+            assert_eq!(Message::parse(line), None, "{}", line);
+        }
+    }
+}
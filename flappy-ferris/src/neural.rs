@@ -0,0 +1,70 @@
+use crate::env::OBSERVATION_SIZE;
+use rand::Rng;
+
+// Used for the shape of the networks: the observation of the env module goes in, one hidden layer and a single output.
+pub const NETWORK_INPUTS: usize = OBSERVATION_SIZE;
+pub const HIDDEN_NEURONS: usize = 8;
+// Every neuron also has a bias, which is its last weight.
+pub const WEIGHT_COUNT: usize = (NETWORK_INPUTS + 1) * HIDDEN_NEURONS + HIDDEN_NEURONS + 1;
+
+// A small neural network that decides if Ferris flaps. All weights are in one list, so networks are easy to breed:
+// first the weights of every hidden neuron (one per input and the bias), then the ones of the output neuron.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Network {
+    pub weights: Vec<f32>,
+}
+impl Network {
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Self {
+            weights: (0..WEIGHT_COUNT)
+                .map(|_| rng.gen_range(-1.0..1.0))
+                .collect(),
+        }
+    }
+
+    // Between -1 and 1.
+    pub fn output(&self, inputs: &[f32]) -> f32 {
+        let (hidden_weights, output_weights) =
+            self.weights.split_at((NETWORK_INPUTS + 1) * HIDDEN_NEURONS);
+
+        let mut sum = output_weights[HIDDEN_NEURONS];
+        for (neuron, weights) in hidden_weights.chunks(NETWORK_INPUTS + 1).enumerate() {
+            let activation = inputs
+                .iter()
+                .zip(weights.iter())
+                .map(|(input, weight)| input * weight)
+                .sum::<f32>()
+                + weights[NETWORK_INPUTS];
+
+            sum += activation.tanh() * output_weights[neuron];
+        }
+
+        sum.tanh()
+    }
+
+    // The network flaps when its output is positive.
+    pub fn flaps(&self, inputs: &[f32]) -> bool {
+        self.output(inputs) > 0.0
+    }
+
+    // A child with each weight taken from one of the two parents.
+    pub fn crossover(&self, other: &Network, rng: &mut impl Rng) -> Network {
+        Network {
+            weights: self
+                .weights
+                .iter()
+                .zip(other.weights.iter())
+                .map(|(&mine, &theirs)| if rng.gen_bool(0.5) { mine } else { theirs })
+                .collect(),
+        }
+    }
+
+    // Changes every weight with the given chance by up to the given size in either direction.
+    pub fn mutate(&mut self, rng: &mut impl Rng, rate: f64, size: f32) {
+        for weight in self.weights.iter_mut() {
+            if rng.gen_bool(rate) {
+                *weight += rng.gen_range(-size..size);
+            }
+        }
+    }
+}
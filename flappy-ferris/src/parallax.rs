@@ -0,0 +1,85 @@
+use crate::assets::Assets;
+use crate::entities::{FLOOR_LEVEL, PIPE_SPEED, SCREEN_WIDTH};
+use ggez::mint::Point2;
+use ggez::{graphics, Context, GameResult};
+
+// A horizontally tiling image that scrolls at a fraction of the pipe speed.
+pub struct ParallaxLayer {
+    // The id of the image in the asset manifest.
+    pub image: &'static str,
+    pub y: f32,
+    // 0.0 stays in place and 1.0 moves together with the pipes.
+    pub speed: f32,
+    pub offset: f32,
+    // Layers in front are drawn over the entities (like the ground, which hides the bottom of the pipes).
+    pub in_front: bool,
+}
+impl ParallaxLayer {
+    pub fn new(image: &'static str, y: f32, speed: f32, in_front: bool) -> Self {
+        Self {
+            image,
+            y,
+            speed,
+            offset: 0.0,
+            in_front,
+        }
+    }
+}
+
+// The background layers, from the farthest to the nearest one.
+pub struct Parallax {
+    pub layers: Vec<ParallaxLayer>,
+}
+impl Parallax {
+    pub fn new() -> Self {
+        Self {
+            layers: vec![
+                ParallaxLayer::new("background", 0.0, 0.0, false),
+                ParallaxLayer::new("layer_hills", 490.0, 0.15, false),
+                ParallaxLayer::new("layer_city", 580.0, 0.4, false),
+                // The top of the ground strip is where the player hits the ground.
+                ParallaxLayer::new("layer_ground", FLOOR_LEVEL, 1.0, true),
+            ],
+        }
+    }
+
+    // Moves the layers, so the ground keeps up with the pipes (boosts included).
+    pub fn update(&mut self, multiplier: f32) {
+        for layer in self.layers.iter_mut() {
+            layer.offset += PIPE_SPEED * multiplier * layer.speed;
+        }
+    }
+
+    // Keeps the offsets within the width of their images, so they stay small (and precise) in long sessions.
+    pub fn wrap(&mut self, assets: &Assets) {
+        for layer in self.layers.iter_mut() {
+            layer.offset %= assets.image(layer.image).width() as f32;
+        }
+    }
+
+    // Draws either the layers behind the entities or the ones in front of them.
+    pub fn draw(&self, ctx: &mut Context, assets: &Assets, in_front: bool) -> GameResult {
+        for layer in self
+            .layers
+            .iter()
+            .filter(|layer| layer.in_front == in_front)
+        {
+            let image = assets.image(layer.image);
+            let width = image.width() as f32;
+
+            // Draws the image as many times as needed to cover the screen, starting from the scrolled position.
+            let mut x = -(layer.offset % width);
+            while x < SCREEN_WIDTH {
+                graphics::draw(
+                    ctx,
+                    image,
+                    graphics::DrawParam::default().dest(Point2 { x, y: layer.y }),
+                )?;
+
+                x += width;
+            }
+        }
+
+        Ok(())
+    }
+}
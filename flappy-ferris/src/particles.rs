@@ -0,0 +1,224 @@
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Color};
+use ggez::mint::{Point2, Vector2};
+use ggez::{Context, GameResult};
+use rand::Rng;
+use std::f32::consts::PI;
+
+// The most particles alive at the same time. New ones are dropped after that.
+pub const MAX_PARTICLES: usize = 2048;
+// The size (in pixels) of the white square every particle is drawn with.
+pub const PARTICLE_TEXTURE_SIZE: u16 = 4;
+
+// A single particle. Velocity and gravity are in pixels per second.
+#[derive(Clone)]
+pub struct Particle {
+    pub position: Point2<f32>,
+    pub velocity: Vector2<f32>,
+    pub gravity: f32,
+    pub life: f32,
+    pub max_life: f32,
+    pub size: f32,
+    pub color: Color,
+}
+
+// Describes how particles are emitted. Every value given as a range is picked at random for each particle.
+#[derive(Clone)]
+pub struct Emitter {
+    pub count: usize,
+    pub speed: (f32, f32),
+    // The direction of movement in radians (0.0 is right, PI / 2.0 is down).
+    pub angle: (f32, f32),
+    pub life: (f32, f32),
+    pub size: (f32, f32),
+    pub gravity: f32,
+    pub colors: Vec<Color>,
+}
+impl Emitter {
+    // Light feathers (or Ferris' orange bits) that float down slowly, used when a life is lost.
+    pub fn feathers() -> Self {
+        Self {
+            count: 24,
+            speed: (40.0, 160.0),
+            angle: (0.0, 2.0 * PI),
+            life: (0.6, 1.2),
+            size: (3.0, 6.0),
+            gravity: 120.0,
+            colors: vec![
+                Color::WHITE,
+                Color::from_rgb(247, 76, 0),
+                Color::from_rgb(255, 160, 90),
+            ],
+        }
+    }
+
+    // A quick ring of particles in the given color, used when a boost is collected.
+    pub fn burst(color: Color) -> Self {
+        Self {
+            count: 40,
+            speed: (150.0, 300.0),
+            angle: (0.0, 2.0 * PI),
+            life: (0.3, 0.6),
+            size: (2.0, 5.0),
+            gravity: 0.0,
+            colors: vec![color, Color::WHITE],
+        }
+    }
+
+    // A few dusty puffs going backwards, emitted every frame while the speed-up boost is active.
+    pub fn dust() -> Self {
+        Self {
+            count: 2,
+            speed: (60.0, 140.0),
+            angle: (PI - 0.3, PI + 0.3),
+            life: (0.3, 0.5),
+            size: (3.0, 7.0),
+            gravity: -30.0,
+            colors: vec![
+                Color::from_rgba(222, 216, 149, 200),
+                Color::from_rgba(255, 255, 255, 160),
+            ],
+        }
+    }
+
+    // A big explosion of fire and smoke, used when the player dies.
+    pub fn explosion() -> Self {
+        Self {
+            count: 160,
+            speed: (80.0, 420.0),
+            angle: (0.0, 2.0 * PI),
+            life: (0.5, 1.4),
+            size: (3.0, 9.0),
+            gravity: 200.0,
+            colors: vec![
+                Color::from_rgb(255, 220, 80),
+                Color::from_rgb(255, 120, 20),
+                Color::from_rgb(200, 40, 10),
+                Color::from_rgba(60, 60, 60, 200),
+            ],
+        }
+    }
+}
+
+// Keeps all particles in one pool and draws them with a single sprite batch.
+// Alive particles are always at the start of the pool, so dead ones are reused without allocating.
+pub struct ParticleSystem {
+    pool: Vec<Particle>,
+    alive: usize,
+    batch: SpriteBatch,
+}
+impl ParticleSystem {
+    pub fn new(ctx: &mut Context) -> GameResult<Self> {
+        let texture = graphics::Image::solid(ctx, PARTICLE_TEXTURE_SIZE, Color::WHITE)?;
+
+        Ok(Self {
+            pool: Vec::with_capacity(MAX_PARTICLES),
+            alive: 0,
+            batch: SpriteBatch::new(texture),
+        })
+    }
+
+    pub fn count(&self) -> usize {
+        self.alive
+    }
+
+    pub fn clear(&mut self) {
+        self.alive = 0;
+    }
+
+    // Spawns the particles of an emitter at the given position.
+    pub fn emit(&mut self, emitter: &Emitter, position: Point2<f32>) {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..emitter.count {
+            if self.alive >= MAX_PARTICLES {
+                return;
+            }
+
+            let angle = random_between(&mut rng, emitter.angle);
+            let speed = random_between(&mut rng, emitter.speed);
+            let life = random_between(&mut rng, emitter.life);
+            let color = if emitter.colors.is_empty() {
+                Color::WHITE
+            } else {
+                emitter.colors[rng.gen_range(0..emitter.colors.len())]
+            };
+
+            let particle = Particle {
+                position,
+                velocity: Vector2 {
+                    x: angle.cos() * speed,
+                    y: angle.sin() * speed,
+                },
+                gravity: emitter.gravity,
+                life,
+                max_life: life,
+                size: random_between(&mut rng, emitter.size),
+                color,
+            };
+
+            if self.alive < self.pool.len() {
+                self.pool[self.alive] = particle;
+            } else {
+                self.pool.push(particle);
+            }
+            self.alive += 1;
+        }
+    }
+
+    // Moves the particles and removes the ones whose life is over (delta is in seconds).
+    pub fn update(&mut self, delta: f32) {
+        let mut index = 0;
+        while index < self.alive {
+            let particle = &mut self.pool[index];
+            particle.life -= delta;
+
+            if particle.life <= 0.0 {
+                // The last alive particle takes the place of the dead one.
+                self.alive -= 1;
+                self.pool.swap(index, self.alive);
+                continue;
+            }
+
+            particle.velocity.y += particle.gravity * delta;
+            particle.position.x += particle.velocity.x * delta;
+            particle.position.y += particle.velocity.y * delta;
+
+            index += 1;
+        }
+    }
+
+    // Draws every alive particle (fading out with its life) in one draw call.
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        if self.alive == 0 {
+            return Ok(());
+        }
+
+        self.batch.clear();
+
+        for particle in self.pool[..self.alive].iter() {
+            let fade = particle.life / particle.max_life;
+            let scale = particle.size / PARTICLE_TEXTURE_SIZE as f32;
+            let mut color = particle.color;
+            color.a *= fade;
+
+            self.batch.add(
+                graphics::DrawParam::default()
+                    .dest(particle.position)
+                    .offset(Point2 { x: 0.5, y: 0.5 })
+                    .scale(Vector2 { x: scale, y: scale })
+                    .color(color),
+            );
+        }
+
+        graphics::draw(ctx, &self.batch, graphics::DrawParam::default())
+    }
+}
+
+fn random_between(rng: &mut impl Rng, range: (f32, f32)) -> f32 {
+    if range.0 < range.1 {
+        rng.gen_range(range.0..range.1)
+    } else {
+        range.0
+    }
+}
@@ -0,0 +1,151 @@
+use crate::entities::{PlayerEntity, SCREEN_WIDTH};
+use crate::events::GameEvent;
+use crate::input::FlapInput;
+use ggez::event::KeyCode;
+use ggez::graphics::Color;
+
+// Used for placing the players next to each other, each one is this much behind the previous one.
+pub const PLAYER_SPACING: f32 = 90.0;
+
+// The ways the game can be played.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GameMode {
+    Single,
+    // Two players with their own lifes and scores. The last one alive wins.
+    Versus,
+    // Two players sharing their lifes and score. A downed partner can be revived.
+    Coop,
+}
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [GameMode::Single, GameMode::Versus, GameMode::Coop];
+
+    pub fn player_count(&self) -> usize {
+        match self {
+            GameMode::Single => 1,
+            GameMode::Versus | GameMode::Coop => 2,
+        }
+    }
+
+    // Players on the same team share their lifes and score.
+    pub fn team_count(&self) -> usize {
+        match self {
+            GameMode::Coop => 1,
+            _ => self.player_count(),
+        }
+    }
+
+    pub fn team_of(&self, player: usize) -> usize {
+        match self {
+            GameMode::Coop => 0,
+            _ => player,
+        }
+    }
+
+    // The key of its name in the string tables.
+    pub fn key(&self) -> &'static str {
+        match self {
+            GameMode::Single => "mode.single",
+            GameMode::Versus => "mode.versus",
+            GameMode::Coop => "mode.coop",
+        }
+    }
+}
+
+// The color and flap key of each player. The first one also flaps with clicks and touches.
+pub fn player_tint(index: usize) -> Color {
+    match index {
+        0 => Color::WHITE,
+        _ => Color::from_rgb(120, 200, 255),
+    }
+}
+
+pub fn player_input(index: usize) -> FlapInput {
+    match index {
+        0 => FlapInput::new(KeyCode::Space),
+        _ => FlapInput::key_only(KeyCode::Up),
+    }
+}
+
+// The lifes and score of one or more players.
+pub struct Team {
+    pub lifes: i128,
+    pub score: i128,
+}
+impl Team {
+    pub fn new() -> Self {
+        Self { lifes: 1, score: 0 }
+    }
+}
+
+// A single Ferris with its own controls, playing for a team.
+pub struct Player {
+    pub index: usize,
+    pub team: usize,
+    pub entity: PlayerEntity,
+    pub input: FlapInput,
+    // In co-op a player who is not alive is downed and can still be revived.
+    pub alive: bool,
+    // Set during the tick in which the player lost a life or crashed with no lifes left.
+    pub hurt: bool,
+    pub crashed: bool,
+}
+impl Player {
+    pub fn new(index: usize, team: usize) -> Self {
+        Self {
+            index,
+            team,
+            entity: Player::spawn(index),
+            input: player_input(index),
+            alive: true,
+            hurt: false,
+            crashed: false,
+        }
+    }
+
+    // One of the many players of the population mode. They all start on the spot of the first player, each in its own team.
+    pub fn in_population(index: usize) -> Self {
+        Self {
+            index,
+            ..Player::new(0, index)
+        }
+    }
+
+    fn spawn(index: usize) -> PlayerEntity {
+        let mut entity = PlayerEntity::at(SCREEN_WIDTH / 4.0 - index as f32 * PLAYER_SPACING);
+        entity.tint = player_tint(index);
+        entity
+    }
+
+    // Puts the player back at the start for a new game. The input is kept, since its keys may still be held.
+    pub fn reset(&mut self) {
+        self.entity = Player::spawn(self.index);
+        self.alive = true;
+        self.hurt = false;
+        self.crashed = false;
+    }
+
+    // Brings a downed player back at the given height (where the partner who revived them is).
+    pub fn revive(&mut self, y: f32) {
+        self.reset();
+        self.entity.place_at(y);
+    }
+
+    // Takes a life of the team after hitting something. Returns if the player survived it,
+    // so the caller can remember the hit (otherwise the same obstacle would hit them again on the next frame).
+    pub fn crash(&mut self, team: &mut Team, events: &mut Vec<GameEvent>) -> bool {
+        if !self.alive || self.crashed {
+            return false;
+        }
+
+        // A team can be out of lifes while one of its players is still flying, the next crash downs them too.
+        team.lifes = (team.lifes - 1).max(0);
+        if team.lifes > 0 {
+            self.hurt = true;
+            events.push(GameEvent::LifeLost);
+            true
+        } else {
+            self.crashed = true;
+            false
+        }
+    }
+}
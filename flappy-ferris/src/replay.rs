@@ -0,0 +1,212 @@
+use crate::assets::read_resource;
+use crate::entities::{PlayState, PlayerEntity};
+use crate::net::GHOST_ALPHA;
+use ggez::graphics::Color;
+use ggez::{filesystem, Context, GameResult};
+use std::io::Write;
+
+// The best run of every seed is saved as /replays/<seed>.replay in the user data folder.
+pub const REPLAYS_PATH: &str = "/replays";
+pub const REPLAY_EXTENSION: &str = "replay";
+
+// A recorded run on a seeded course. The course is the same every time, so the flaps are enough to play it again,
+// together with where the run started (the player moves on the start screen before the first flap) and where
+// the player was put back after losing a life. For example:
+//   start 371.5 -9.0 0
+//   flaps 0000011000000000011100000
+//   place 2103 384.0
+//   points 95 310 522
+pub struct Replay {
+    // The height, velocity and if the player could flap right away when the run started.
+    pub start: (f32, f32, bool),
+    pub flaps: Vec<bool>,
+    // The ticks in which the player was moved somewhere else and the height they were moved to.
+    pub places: Vec<(u64, f32)>,
+    // The ticks in which a point was scored.
+    pub points: Vec<u64>,
+}
+impl Replay {
+    // Starts recording a run from where the player is.
+    pub fn starting_from(entity: &PlayerEntity) -> Self {
+        Self {
+            start: (entity.position.y, entity.physics.velocity, entity.can_jump),
+            flaps: Vec::new(),
+            places: Vec::new(),
+            points: Vec::new(),
+        }
+    }
+
+    // The tick being recorded at the moment.
+    pub fn tick(&self) -> u64 {
+        self.flaps.len() as u64
+    }
+
+    // Ends the current tick with the flap of the player in it.
+    pub fn record(&mut self, flap: bool) {
+        self.flaps.push(flap);
+    }
+
+    pub fn record_place(&mut self, y: f32) {
+        self.places.push((self.tick(), y));
+    }
+
+    pub fn record_point(&mut self) {
+        self.points.push(self.tick());
+    }
+
+    pub fn score(&self) -> i128 {
+        self.points.len() as i128
+    }
+
+    // The score after the given number of ticks.
+    pub fn score_after(&self, ticks: u64) -> i128 {
+        self.points.iter().filter(|&&tick| tick < ticks).count() as i128
+    }
+
+    pub fn encode(&self) -> String {
+        let (y, velocity, can_jump) = self.start;
+        let flaps: String = self
+            .flaps
+            .iter()
+            .map(|&flap| if flap { '1' } else { '0' })
+            .collect();
+        let points: Vec<String> = self.points.iter().map(|tick| tick.to_string()).collect();
+
+        let mut lines = vec![
+            format!("start {} {} {}", y, velocity, can_jump as u8),
+            format!("flaps {}", flaps),
+        ];
+        for (tick, y) in self.places.iter() {
+            lines.push(format!("place {} {}", tick, y));
+        }
+        lines.push(format!("points {}", points.join(" ")));
+
+        lines.join("\n") + "\n"
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut replay = Self {
+            start: (0.0, 0.0, true),
+            flaps: Vec::new(),
+            places: Vec::new(),
+            points: Vec::new(),
+        };
+
+        for (number, line) in source.lines().enumerate() {
+            let invalid = || format!("line {}: invalid '{}'", number + 1, line);
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            match words.as_slice() {
+                [] => (),
+                ["start", y, velocity, can_jump] => {
+                    replay.start = (
+                        y.parse().map_err(|_| invalid())?,
+                        velocity.parse().map_err(|_| invalid())?,
+                        *can_jump == "1",
+                    );
+                }
+                ["flaps"] => (),
+                ["flaps", flaps] => {
+                    replay.flaps = flaps
+                        .chars()
+                        .map(|flap| match flap {
+                            '0' => Ok(false),
+                            '1' => Ok(true),
+                            _ => Err(invalid()),
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                ["place", tick, y] => replay.places.push((
+                    tick.parse().map_err(|_| invalid())?,
+                    y.parse().map_err(|_| invalid())?,
+                )),
+                ["points", points @ ..] => {
+                    replay.points = points
+                        .iter()
+                        .map(|tick| tick.parse().map_err(|_| invalid()))
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(replay)
+    }
+
+    pub fn path(seed: u64) -> String {
+        format!("{}/{}.{}", REPLAYS_PATH, seed, REPLAY_EXTENSION)
+    }
+
+    // The best run recorded on the seed. A missing or broken replay is the same as none.
+    pub fn load(ctx: &mut Context, seed: u64) -> Option<Self> {
+        let source = read_resource(ctx, &Replay::path(seed)).ok()?;
+
+        match Replay::parse(&source) {
+            Ok(replay) => Some(replay),
+            Err(error) => {
+                eprintln!("Could not read replay of seed {}: {}", seed, error);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, ctx: &mut Context, seed: u64) -> GameResult {
+        filesystem::create_dir(ctx, REPLAYS_PATH)?;
+        let mut file = filesystem::create(ctx, Replay::path(seed))?;
+        file.write_all(self.encode().as_bytes())?;
+
+        Ok(())
+    }
+}
+
+// A translucent Ferris flying the best recorded run of the course.
+pub struct ReplayGhost {
+    pub entity: PlayerEntity,
+    pub replay: Replay,
+    // The next tick to replay.
+    tick: u64,
+}
+impl ReplayGhost {
+    pub fn new(replay: Replay) -> Self {
+        let mut entity = PlayerEntity::new();
+        let (y, velocity, can_jump) = replay.start;
+        entity.place_at(y);
+        entity.physics.velocity = velocity;
+        entity.can_jump = can_jump;
+        entity.tint = Color::new(1.0, 1.0, 1.0, GHOST_ALPHA);
+
+        Self {
+            entity,
+            replay,
+            tick: 0,
+        }
+    }
+
+    // Plays the next tick of the run (nothing after its end, when the ghost has crashed).
+    pub fn update(&mut self) {
+        let flap = match self.replay.flaps.get(self.tick as usize) {
+            Some(&flap) => flap,
+            None => return,
+        };
+
+        self.entity.update(flap, &PlayState::Play);
+        for (_, y) in self
+            .replay
+            .places
+            .iter()
+            .filter(|(tick, _)| *tick == self.tick)
+        {
+            self.entity.place_at(*y);
+        }
+        self.tick += 1;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick as usize >= self.replay.flaps.len()
+    }
+
+    // The score of the best run at the moment it is replaying.
+    pub fn score(&self) -> i128 {
+        self.replay.score_after(self.tick)
+    }
+}
@@ -0,0 +1,540 @@
+use crate::entities::{
+    BoostEntity, BoostType, EnemyEntity, PipeEntity, PlayState, BOOST_WIDTH, ENEMY_WIDTH,
+    PIPE_WIDTH,
+};
+use crate::events::GameEvent;
+use crate::players::{GameMode, Player, Team};
+use ggez::mint::Point2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+
+// The game runs this many ticks every second, each one a fixed amount of time long.
+// This way the same flaps always give the same game, which the online races and replays rely on.
+pub const TICKS_PER_SECOND: u32 = 60;
+pub const TICK_SECONDS: f32 = 1.0 / TICKS_PER_SECOND as f32;
+pub const TICK_NANOS: f32 = 1000000000.0 / TICKS_PER_SECOND as f32;
+
+pub const BOOST_DURATION: f32 = 10000000000.0;
+// How long (in ns) a downed co-op partner waits for a revive token (also before another one if it was missed).
+pub const REVIVE_DELAY: f32 = 3000000000.0;
+
+// What happened during a tick besides the game events, for the effects and the replays.
+pub struct StepResult {
+    // The boosts taken and where they were.
+    pub collected: Vec<(BoostType, Point2<f32>)>,
+    // The players put back in the middle after touching the ground (with a spare life) and their new height.
+    pub placed: Vec<(usize, f32)>,
+    // Set in the tick in which the last player crashed.
+    pub over: bool,
+}
+
+// The rules of the game: the players, their teams and the course of pipes, enemies and boosts they fly through.
+// It does not need a window, so it can also be run without one (e.g. by the autopilot benchmark).
+pub struct Simulation {
+    pub mode: GameMode,
+    pub players: Vec<Player>,
+    pub teams: Vec<Team>,
+    // The player who won the last game with more than one player (None for a draw).
+    pub winner: Option<usize>,
+
+    pub play_state: PlayState,
+
+    pub pipes: VecDeque<PipeEntity>,
+    pub enemies: VecDeque<EnemyEntity>,
+    pub boosts: VecDeque<BoostEntity>,
+
+    pub time_until_next_pipe: f32,
+    pub time_until_next_enemy: f32,
+    pub time_until_next_boost: f32,
+    pub time_until_next_revive: f32,
+
+    pub boost_duration: f32,
+    pub multiplier: f32,
+
+    pub has_boost: bool,
+    // Off in the population mode, where a boost taken by one player would change the game of all the others.
+    pub spawns_boosts: bool,
+
+    // The pipes, enemies and boosts each have their own random numbers, so taking a boost does not change where the next pipes are.
+    // They are seeded with the seed of the race in online races (or the one given with --seed), so everyone gets the same course.
+    pipe_rng: StdRng,
+    enemy_rng: StdRng,
+    boost_rng: StdRng,
+}
+impl Simulation {
+    pub fn new(mode: GameMode) -> Self {
+        let mut sim = Self {
+            mode,
+            players: Vec::new(),
+            teams: Vec::new(),
+            winner: None,
+
+            play_state: PlayState::StartScreen,
+
+            pipes: VecDeque::new(),
+            enemies: VecDeque::new(),
+            boosts: VecDeque::new(),
+
+            // Time until each new entity is stored in ns and each tick's length is subtracted.
+            time_until_next_pipe: 1000000000.0,
+            time_until_next_enemy: 10000000000.0,
+            time_until_next_boost: 10000000000.0,
+            time_until_next_revive: REVIVE_DELAY,
+
+            boost_duration: 0.0,
+            multiplier: 1.0,
+
+            has_boost: false,
+            spawns_boosts: true,
+
+            pipe_rng: StdRng::from_entropy(),
+            enemy_rng: StdRng::from_entropy(),
+            boost_rng: StdRng::from_entropy(),
+        };
+        sim.set_mode(mode);
+
+        sim
+    }
+
+    // Makes the pipes, enemies and boosts from now on the ones of the seed.
+    pub fn seed(&mut self, seed: u64) {
+        self.pipe_rng = StdRng::seed_from_u64(seed);
+        self.enemy_rng = StdRng::seed_from_u64(seed.wrapping_add(1));
+        self.boost_rng = StdRng::seed_from_u64(seed.wrapping_add(2));
+    }
+
+    // Removes all pipes, enemies and boosts and starts counting down to the first ones again.
+    pub fn clear_course(&mut self) {
+        self.pipes = VecDeque::new();
+        self.enemies = VecDeque::new();
+        self.boosts = VecDeque::new();
+
+        self.time_until_next_pipe = 1000000000.0;
+        self.time_until_next_enemy = 10000000000.0;
+        self.time_until_next_boost = 10000000000.0;
+        self.time_until_next_revive = REVIVE_DELAY;
+
+        self.boost_duration = 0.0;
+        self.multiplier = 1.0;
+
+        self.has_boost = false;
+    }
+
+    // Resets everything for a new game (the scores should be read before that).
+    pub fn restart(&mut self) {
+        self.clear_course();
+
+        // In order to actually display the GameOver logo instead of the StartScreen one.
+        // The players who crashed lie where they fell until the flap that starts the next game (see step).
+        self.play_state = PlayState::Dead;
+
+        for team in self.teams.iter_mut() {
+            *team = Team::new();
+        }
+    }
+
+    // Puts every player back at the start.
+    pub fn respawn(&mut self) {
+        for player in self.players.iter_mut() {
+            player.reset();
+        }
+    }
+
+    // Switches to another game mode, adding or removing players and putting them in teams.
+    pub fn set_mode(&mut self, mode: GameMode) {
+        self.mode = mode;
+        self.players.truncate(mode.player_count());
+        while self.players.len() < mode.player_count() {
+            self.players.push(Player::new(self.players.len(), 0));
+        }
+        for player in self.players.iter_mut() {
+            player.team = mode.team_of(player.index);
+        }
+
+        self.teams = (0..mode.team_count()).map(|_| Team::new()).collect();
+    }
+
+    // Fills the game with the given number of players flying on their own (for the population mode).
+    // Players never touch each other, so the cost of a tick only grows with their number. Obstacles stay for everyone
+    // after one of them is hit and there are no boosts, so how far each player gets does not depend on the others.
+    pub fn set_population(&mut self, count: usize) {
+        self.mode = GameMode::Versus;
+        self.spawns_boosts = false;
+        self.players = (0..count).map(Player::in_population).collect();
+        self.teams = (0..count).map(|_| Team::new()).collect();
+    }
+
+    // The score of the team a player is in.
+    pub fn score_of(&self, player: usize) -> i128 {
+        self.teams[self.players[player].team].score
+    }
+
+    // The last one alive wins. If the last players crash at the same moment, the one with the most points wins.
+    fn find_winner(&self, last: &[usize]) -> Option<usize> {
+        let best = last.iter().map(|&index| self.score_of(index)).max()?;
+        let mut best_players = last.iter().filter(|&&index| self.score_of(index) == best);
+
+        match (best_players.next(), best_players.next()) {
+            (Some(&index), None) => Some(index),
+            _ => None,
+        }
+    }
+
+    // The boost that changes the speed at the moment (bonus lifes are used right away).
+    pub fn active_boost(&self) -> Option<BoostType> {
+        if !self.has_boost {
+            None
+        } else if self.multiplier > 1.0 {
+            Some(BoostType::SpeedUp)
+        } else {
+            Some(BoostType::SlowDown)
+        }
+    }
+
+    // Checks if all players lost the current game.
+    pub fn is_over(&self) -> bool {
+        self.play_state.is_playing() && self.players.iter().all(|player| !player.alive)
+    }
+
+    // Runs a single tick of the game with the flap input of every player (held down or not).
+    // On the start screen pipes and enemies still come along for the attract mode, but they can not be hit.
+    pub fn step(&mut self, flaps: &[bool], events: &mut Vec<GameEvent>) -> StepResult {
+        let mut result = StepResult {
+            collected: Vec::new(),
+            placed: Vec::new(),
+            over: false,
+        };
+        // The flap of the first player after a game brings everyone back for the next one.
+        if self.play_state == PlayState::Dead && flaps.first().copied().unwrap_or(false) {
+            self.respawn();
+        }
+
+        let playing = self.play_state.is_playing();
+        let attract = self.play_state == PlayState::StartScreen;
+
+        // Subtracts length of a tick from countdown fields (only if in Play state or the attract mode).
+        let delta = TICK_NANOS;
+        if playing || attract {
+            self.time_until_next_pipe -= delta;
+            self.time_until_next_enemy -= delta;
+        }
+        if playing {
+            self.time_until_next_boost -= delta;
+        }
+
+        // Removes boost if there is an active one and the countdown is over.
+        if self.has_boost {
+            self.boost_duration -= delta;
+
+            if self.boost_duration <= 0.0 {
+                self.has_boost = false;
+                events.push(GameEvent::BoostExpired);
+
+                self.multiplier = 1.0;
+            }
+        }
+
+        // Generates a new pipe and resets the countdown until the next one.
+        if (playing || attract) && self.time_until_next_pipe <= 0.0 {
+            let random_y = self.pipe_rng.gen_range(67.0..481.0);
+
+            let pipe = PipeEntity::new(random_y);
+            self.pipes.push_back(pipe);
+
+            self.time_until_next_pipe = self.pipe_rng.gen_range(1.0..4.5) * 1000000000.0;
+        }
+
+        // Generates a new enemy and resets the countdown until the next one.
+        if (playing || attract) && self.time_until_next_enemy <= 0.0 {
+            let random_y = self.enemy_rng.gen_range(63.0..705.0);
+
+            let enemy = EnemyEntity::new(random_y);
+            self.enemies.push_back(enemy);
+
+            self.time_until_next_enemy = self.enemy_rng.gen_range(6.0..12.0) * 1000000000.0;
+        }
+
+        // Create a new boost (if there are no active ones at the moment) and resets the countdown until the next one.
+        if playing
+            && self.spawns_boosts
+            && self.time_until_next_boost <= 0.0
+            && self.has_boost == false
+        {
+            // The second random value is used for determining the type of the newly created boost.
+            let random_y = self.boost_rng.gen_range(48.0..720.0);
+            let random_val = self.boost_rng.gen_range(0.0..18.0);
+
+            let boost = BoostEntity::new(random_y, random_val);
+            self.boosts.push_back(boost);
+
+            self.time_until_next_boost = self.boost_rng.gen_range(10.0..30.0) * 1000000000.0;
+        }
+
+        // In co-op a revive token comes for a downed partner while the other one is still flying.
+        let downed = self.players.iter().any(|player| !player.alive);
+        if self.mode == GameMode::Coop && playing && downed {
+            self.time_until_next_revive -= delta;
+
+            let on_screen = self
+                .boosts
+                .iter()
+                .any(|boost| boost.effect == BoostType::Revive);
+            if self.time_until_next_revive <= 0.0 && !on_screen {
+                let random_y = self.boost_rng.gen_range(48.0..720.0);
+                self.boosts
+                    .push_back(BoostEntity::with_effect(random_y, BoostType::Revive));
+
+                self.time_until_next_revive = REVIVE_DELAY;
+            }
+        } else {
+            self.time_until_next_revive = REVIVE_DELAY;
+        }
+
+        // Updates every player. Players who are down can not flap.
+        for player in self.players.iter_mut() {
+            player.hurt = false;
+            player.crashed = false;
+
+            let flap_held = flaps.get(player.index).copied().unwrap_or(false) && player.alive;
+            player.entity.update(flap_held, &self.play_state);
+            if !player.alive {
+                player.entity.land();
+            }
+
+            if player.entity.has_jumped {
+                events.push(GameEvent::Flap);
+            }
+
+            // Checks if the player touches the ground and has a spare life to use.
+            if playing
+                && player.entity.hits_ground()
+                && player.crash(&mut self.teams[player.team], events)
+            {
+                player.entity.prevent_hitting_ground();
+                result.placed.push((player.index, player.entity.position.y));
+            }
+        }
+
+        // Updates pipes and marks these that need to be removed.
+        for pipe in self.pipes.iter_mut() {
+            pipe.update(self.multiplier);
+
+            let pos = pipe.position;
+            if !playing {
+                pipe.is_passed = pos.x <= -(PIPE_WIDTH / 2.0);
+                continue;
+            }
+
+            // Each player can only be hit once by a pipe. It stays there for the other players.
+            for player in self.players.iter_mut().filter(|player| player.alive) {
+                if !pipe.hit_by.contains(&player.index)
+                    && (player.entity.zone.overlaps(&pipe.bottom_zone)
+                        || player.entity.zone.overlaps(&pipe.top_zone))
+                    && player.crash(&mut self.teams[player.team], events)
+                {
+                    pipe.hit_by.push(player.index);
+                }
+            }
+
+            // Every player still alive who did not hit it gets a point.
+            if pos.x <= -(PIPE_WIDTH / 2.0) {
+                for player in self
+                    .players
+                    .iter()
+                    .filter(|player| player.alive && !pipe.hit_by.contains(&player.index))
+                {
+                    self.teams[player.team].score += 1;
+                    events.push(GameEvent::PipePassed {
+                        player: player.index,
+                    });
+                }
+                pipe.is_passed = true;
+            }
+        }
+
+        // Updates enemies and marks these that need to be removed.
+        let front = self.players[0].entity.position.x;
+        for enemy in self.enemies.iter_mut() {
+            enemy.update(self.multiplier);
+
+            let pos = enemy.position;
+            if pos.x <= -(ENEMY_WIDTH / 2.0) {
+                enemy.is_passed = true;
+            }
+            if !playing {
+                continue;
+            }
+
+            for player in self.players.iter_mut().filter(|player| player.alive) {
+                if !enemy.hit_by.contains(&player.index)
+                    && player.entity.zone.overlaps(&enemy.zone)
+                    && player.crash(&mut self.teams[player.team], events)
+                {
+                    enemy.hit_by.push(player.index);
+                }
+            }
+
+            if !enemy.is_behind && pos.x < front {
+                enemy.is_behind = true;
+                events.push(GameEvent::EnemyPassed);
+            }
+        }
+
+        // Updates boosts and marks these that need to be removed.
+        let mut revive_at = None;
+        for boost in self.boosts.iter_mut() {
+            boost.update();
+
+            let pos = boost.position;
+
+            // A boost is taken by the first player to touch it. Bonus lifes go to the team of that player, speed changes affect everyone.
+            let collector = self
+                .players
+                .iter_mut()
+                .find(|player| player.alive && player.entity.zone.overlaps(&boost.zone));
+
+            if let Some(player) = collector.filter(|_| playing) {
+                boost.is_collected = true;
+                events.push(GameEvent::BoostCollected);
+                result.collected.push((boost.effect, boost.position));
+
+                match boost.effect {
+                    BoostType::BonusLife => {
+                        self.teams[player.team].lifes += 1;
+                    }
+                    BoostType::Revive => {
+                        revive_at = Some(player.entity.position.y);
+                    }
+                    BoostType::SlowDown => {
+                        self.has_boost = true;
+                        self.boost_duration = BOOST_DURATION;
+                        self.multiplier = 0.5;
+                    }
+                    BoostType::SpeedUp => {
+                        self.has_boost = true;
+                        self.boost_duration = BOOST_DURATION;
+                        self.multiplier = 1.5;
+                    }
+                };
+            }
+
+            if pos.x <= -(BOOST_WIDTH / 2.0) {
+                boost.is_passed = true;
+            }
+        }
+
+        // Brings the downed partners back next to the one who took the revive token.
+        if let Some(y) = revive_at {
+            for player in self.players.iter_mut().filter(|player| !player.alive) {
+                player.revive(y);
+            }
+        }
+
+        // Takes the players who crashed out of the game and starts the animations of the ones who got hurt.
+        let mut crashed = Vec::new();
+        for player in self.players.iter_mut() {
+            if player.crashed {
+                player.alive = false;
+                crashed.push(player.index);
+                events.push(GameEvent::Death);
+
+                player.entity.die();
+            } else if player.hurt {
+                player.entity.hurt();
+            }
+        }
+
+        // Checks if the game is over.
+        if self.is_over() {
+            self.winner = self.find_winner(&crashed);
+            self.play_state.set_dead();
+            result.over = true;
+        }
+
+        // Removes all pipes that are already passed.
+        self.pipes.retain(|pipe| pipe.is_passed == false);
+
+        // Removes all enemies that are already passed.
+        self.enemies.retain(|enemy| enemy.is_passed == false);
+
+        // Removes all boosts that are already passed or collected.
+        self.boosts
+            .retain(|boost| boost.is_passed == false && boost.is_collected == false);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::MIDDLE;
+
+    // A game with only the pipe of the test on the course.
+    fn game_with_pipe(mode: GameMode, y: f32) -> Simulation {
+        let mut sim = Simulation::new(mode);
+        sim.play_state = PlayState::Play;
+        sim.time_until_next_pipe = f32::MAX;
+        sim.time_until_next_enemy = f32::MAX;
+        sim.time_until_next_boost = f32::MAX;
+        sim.pipes.push_back(PipeEntity::new(y));
+
+        sim
+    }
+
+    // Flies every player until the pipe is gone, each one flapping whenever it falls below its own height.
+    fn fly_past_pipe(sim: &mut Simulation, heights: &[f32]) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        for _ in 0..1000 {
+            if sim.pipes.is_empty() {
+                break;
+            }
+
+            let flaps: Vec<bool> = sim
+                .players
+                .iter()
+                .map(|player| player.entity.position.y >= heights[player.index])
+                .collect();
+            sim.step(&flaps, &mut events);
+        }
+        assert!(sim.pipes.is_empty());
+
+        events
+    }
+
+    fn points(events: &[GameEvent]) -> Vec<GameEvent> {
+        events
+            .iter()
+            .filter(|event| matches!(event, GameEvent::PipePassed { .. }))
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn hitting_a_pipe_gives_no_point() {
+        // The player flies into the bottom pipe and survives it with a spare life.
+        let mut sim = game_with_pipe(GameMode::Single, MIDDLE - 90.0);
+        sim.teams[0].lifes = 2;
+
+        let events = fly_past_pipe(&mut sim, &[MIDDLE + 200.0]);
+
+        assert_eq!(sim.teams[0].lifes, 1);
+        assert_eq!(sim.teams[0].score, 0);
+        assert_eq!(points(&events), Vec::new());
+    }
+
+    #[test]
+    fn only_the_players_who_pass_a_pipe_score() {
+        // The first player hits the bottom pipe, the second one flies through the gap.
+        let mut sim = game_with_pipe(GameMode::Versus, MIDDLE - 90.0);
+        sim.teams[0].lifes = 2;
+
+        let events = fly_past_pipe(&mut sim, &[MIDDLE + 200.0, MIDDLE]);
+
+        assert_eq!(sim.teams[0].lifes, 1);
+        assert_eq!(sim.teams[0].score, 0);
+        assert_eq!(sim.teams[1].score, 1);
+        assert_eq!(points(&events), vec![GameEvent::PipePassed { player: 1 }]);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::entities::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use ggez::conf::FullscreenType;
+use ggez::graphics::{self, Rect};
+use ggez::mint::{Point2, Vector2};
+use ggez::{Context, GameResult};
+
+// How the logical SCREEN_WIDTH x SCREEN_HEIGHT game area is fitted inside the window.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScaleMode {
+    // Keeps the aspect ratio and covers the leftover space with black bars.
+    Letterbox,
+    // Stretches the game area over the whole window.
+    Stretch,
+}
+
+// Maps the game coordinates (the ones all entities use) to the actual window.
+pub struct Viewport {
+    pub mode: ScaleMode,
+    pub fullscreen: bool,
+    pub window_width: f32,
+    pub window_height: f32,
+    // The part of the game coordinate space that ends up on the window, bars included.
+    pub visible: Rect,
+}
+impl Viewport {
+    pub fn new() -> Self {
+        Self {
+            mode: ScaleMode::Letterbox,
+            fullscreen: false,
+            window_width: SCREEN_WIDTH,
+            window_height: SCREEN_HEIGHT,
+            visible: Rect::new(0.0, 0.0, SCREEN_WIDTH, SCREEN_HEIGHT),
+        }
+    }
+
+    // Recalculates the visible area every time the window changes its size.
+    pub fn resize(&mut self, ctx: &mut Context, width: f32, height: f32) -> GameResult {
+        // A minimized window has no size, so the last visible area is kept until it comes back.
+        if width <= 0.0 || height <= 0.0 {
+            return Ok(());
+        }
+
+        self.window_width = width;
+        self.window_height = height;
+
+        self.visible = match self.mode {
+            ScaleMode::Stretch => Rect::new(0.0, 0.0, SCREEN_WIDTH, SCREEN_HEIGHT),
+            ScaleMode::Letterbox => {
+                let scale = (width / SCREEN_WIDTH).min(height / SCREEN_HEIGHT);
+                let w = width / scale;
+                let h = height / scale;
+
+                Rect::new(-(w - SCREEN_WIDTH) / 2.0, -(h - SCREEN_HEIGHT) / 2.0, w, h)
+            }
+        };
+
+        graphics::set_screen_coordinates(ctx, self.visible)
+    }
+
+    // Moves the camera by the given offset (e.g. for shaking the screen). A zero offset puts it back in place.
+    pub fn apply_offset(&self, ctx: &mut Context, offset: Vector2<f32>) -> GameResult {
+        let mut coordinates = self.visible;
+        coordinates.translate(Vector2 {
+            x: -offset.x,
+            y: -offset.y,
+        });
+
+        graphics::set_screen_coordinates(ctx, coordinates)
+    }
+
+    // Switches between letterboxing and stretching.
+    pub fn toggle_mode(&mut self, ctx: &mut Context) -> GameResult {
+        self.mode = match self.mode {
+            ScaleMode::Letterbox => ScaleMode::Stretch,
+            ScaleMode::Stretch => ScaleMode::Letterbox,
+        };
+
+        self.resize(ctx, self.window_width, self.window_height)
+    }
+
+    // The window sends a resize event after this, so the visible area is recalculated there.
+    pub fn toggle_fullscreen(&mut self, ctx: &mut Context) -> GameResult {
+        self.fullscreen = !self.fullscreen;
+
+        let fullscreen_type = if self.fullscreen {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Windowed
+        };
+
+        graphics::set_fullscreen(ctx, fullscreen_type)
+    }
+
+    // Converts a point in window pixels (e.g. a click) to game coordinates.
+    pub fn to_game(&self, point: Point2<f32>) -> Point2<f32> {
+        Point2 {
+            x: self.visible.x + point.x / self.window_width * self.visible.w,
+            y: self.visible.y + point.y / self.window_height * self.visible.h,
+        }
+    }
+
+    // Hides everything drawn outside of the game area.
+    pub fn draw_bars(&self, ctx: &mut Context) -> GameResult {
+        let v = self.visible;
+        let bars = [
+            Rect::new(v.x, v.y, -v.x, v.h),
+            Rect::new(SCREEN_WIDTH, v.y, v.x + v.w - SCREEN_WIDTH, v.h),
+            Rect::new(v.x, v.y, v.w, -v.y),
+            Rect::new(v.x, SCREEN_HEIGHT, v.w, v.y + v.h - SCREEN_HEIGHT),
+        ];
+
+        let mut builder = graphics::MeshBuilder::new();
+        let mut has_bars = false;
+        for bar in bars.iter().filter(|bar| bar.w > 0.0 && bar.h > 0.0) {
+            builder.rectangle(graphics::DrawMode::fill(), *bar, graphics::Color::BLACK)?;
+            has_bars = true;
+        }
+
+        if has_bars {
+            let mesh = builder.build(ctx)?;
+            graphics::draw(ctx, &mesh, graphics::DrawParam::default())?;
+        }
+
+        Ok(())
+    }
+}
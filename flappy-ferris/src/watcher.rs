@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// How often the resources folder is checked for changes (in seconds).
+pub const WATCH_INTERVAL: f32 = 0.5;
+
+// Watches the resources folder for changed files. Only used while developing, since it checks every file on disk.
+pub struct ResourceWatcher {
+    root: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+    time_until_next_check: f32,
+}
+impl ResourceWatcher {
+    pub fn new(root: PathBuf) -> Self {
+        let mut modified = HashMap::new();
+        scan(&root, &mut modified);
+
+        Self {
+            root,
+            modified,
+            time_until_next_check: WATCH_INTERVAL,
+        }
+    }
+
+    // Returns the files that were changed or added since the last check.
+    // The paths are the ones used by ggez for resources, e.g. "/enemy_sheet.png".
+    pub fn changed_files(&mut self, delta: f32) -> Vec<String> {
+        self.time_until_next_check -= delta;
+        if self.time_until_next_check > 0.0 {
+            return Vec::new();
+        }
+
+        self.time_until_next_check = WATCH_INTERVAL;
+
+        let mut current = HashMap::new();
+        scan(&self.root, &mut current);
+
+        let changed = current
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(*time))
+            .filter_map(|(path, _)| self.resource_path(path))
+            .collect();
+
+        self.modified = current;
+
+        changed
+    }
+
+    fn resource_path(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let parts = relative
+            .components()
+            .map(|part| part.as_os_str().to_str())
+            .collect::<Option<Vec<&str>>>()?;
+
+        Some(format!("/{}", parts.join("/")))
+    }
+}
+
+// Collects the last modification time of every file in the folder and its subfolders.
+fn scan(folder: &Path, files: &mut HashMap<PathBuf, SystemTime>) {
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            scan(&path, files);
+        } else if let Ok(modified) = entry.metadata().and_then(|metadata| metadata.modified()) {
+            files.insert(path, modified);
+        }
+    }
+}